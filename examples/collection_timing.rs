@@ -0,0 +1,42 @@
+//! Compare how long the unix probes take to read `/proc`, sequentially and in
+//! parallel
+//!
+//! `cargo run --release --example collection_timing -- [THREADS]`
+
+#[cfg(unix)]
+fn main() {
+    use libprocess_server::{
+        AcquisitionOptions,
+        ProcRoot,
+        ProcessProbe,
+        UnixManualProbe,
+        UnixProbeConfig,
+        UnixProcfsProbe,
+        UnixPsutilProbe,
+    };
+
+    let parallel = std::env::args().nth(1).and_then(|x| x.parse().ok()).unwrap_or(4);
+    for threads in [1, parallel] {
+        let root = ProcRoot::new(&UnixProbeConfig {
+            threads,
+            ..Default::default()
+        });
+        let options = AcquisitionOptions::default();
+        let timings = [
+            ("manual", (UnixManualProbe::new(root.clone()).collector(options))()),
+            ("procfs", (UnixProcfsProbe::new(root.clone()).collector(options))()),
+            ("psutil", (UnixPsutilProbe::new(root).collector(options))()),
+        ];
+        for (probe, collection) in timings {
+            match collection {
+                Ok(collection) => println!("{probe} {:?}", collection.timing),
+                Err(e) => println!("{probe} failed {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("Only the unix probes can be compared");
+}
@@ -7,9 +7,11 @@ pub trait Cache<T> {
     fn get(&self) -> Vec<&T>;
 }
 
-pub trait ChannelCache<T: Clone>: Cache<T> {
-    fn subscribe(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<T>;
-    fn send(&mut self, value: T);
+/// A cache that notifies its subscribers, the messages sent can differ from the
+/// values cached (for example an event describing what happened to a value)
+pub trait ChannelCache<T: Clone, M: Clone = T>: Cache<T> {
+    fn subscribe(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<M>;
+    fn send(&mut self, value: M);
 }
 
 pub trait AsCache<T> {
@@ -18,10 +20,10 @@ pub trait AsCache<T> {
     fn cache_mut(&mut self) -> &mut Self::Cache;
 }
 
-pub trait AsChannelCache<T: Clone> {
-    type Cache: ChannelCache<T>;
-    fn cache(&self) -> &<Self as AsChannelCache<T>>::Cache;
-    fn cache_mut(&mut self) -> &mut <Self as AsChannelCache<T>>::Cache;
+pub trait AsChannelCache<T: Clone, M: Clone = T> {
+    type Cache: ChannelCache<T, M>;
+    fn cache(&self) -> &<Self as AsChannelCache<T, M>>::Cache;
+    fn cache_mut(&mut self) -> &mut <Self as AsChannelCache<T, M>>::Cache;
 }
 
 // Note when negative impl are fully implemented we will be able to do this
@@ -8,6 +8,9 @@ pub enum Error {
     #[cfg(unix)]
     #[error("Error while calling psutil: {0}")]
     PsUtilError(#[from] psutil::process::ProcessError),
+    #[cfg(unix)]
+    #[error("Error while calling psutil host: {0}")]
+    PsUtilHostError(#[from] psutil::Error),
     #[error("Error while parsing Unix stat: {0}")]
     InvalidUnixStat(String),
     #[error("User not found: {0}")]
@@ -16,6 +19,8 @@ pub enum Error {
     IOError(#[from] std::io::Error),
     #[error("Missing pid: {0}")]
     MissingPid(u32),
+    #[error("Invalid snapshot: {0}")]
+    InvalidSnapshot(#[from] serde_json::Error),
     #[error("Not yet implemented for {0}")]
     Unimplemented(String),
 }
@@ -2,11 +2,13 @@ mod cache;
 mod error;
 mod manager;
 mod process;
+mod scheduler;
 mod server;
 
 pub use cache::{AsCache, AsChannelCache, Cache, ChannelCache};
 pub use manager::*;
 pub use process::*;
+pub use scheduler::{Scheduler, SchedulerConfig};
 pub use server::*;
 
 pub use error::{Error as ProcessServerError, Result as ProcessServerResult};
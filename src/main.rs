@@ -1,11 +1,11 @@
-use libprocess_server::{Manager, ManagerConfig, Server, WarpServer, WarpServerConfig, WarpServerConfigBuilder};
+use libprocess_server::{DedupPolicy, Manager, ManagerConfig, SchedulerConfig, Server, SnapshotDiff, WarpServer, WarpServerConfig, WarpServerConfigBuilder};
 use parking_lot::RwLock;
-use std::{net::IpAddr, sync::Arc};
+use std::{net::IpAddr, path::PathBuf, sync::Arc};
 use std::str::FromStr;
 use tracing_subscriber::util::SubscriberInitExt;
 use clap::*;
 #[cfg(unix)]
-use libprocess_server::UnixProbe;
+use libprocess_server::{UnixProbe, UnixProbeConfig};
 #[cfg(windows)]
 use libprocess_server::WindowsProbe;
 
@@ -22,6 +22,88 @@ struct Cli {
     #[cfg(unix)]
     #[arg(value_enum, default_value_t = ProbeType::Manual)]
     probe_type: ProbeType,
+    /// Forget the processes sent on `/data` that were not seen in that many acquisitions
+    /// (16 by default)
+    #[arg(long)]
+    dedup_max_age: Option<u64>,
+    /// Maximum number of processes remembered to deduplicate `/data`
+    #[arg(long)]
+    dedup_capacity: Option<usize>,
+    /// How many acquisitions are kept in memory (see `/snapshots`)
+    #[arg(long, default_value_t = 10)]
+    history: usize,
+    /// The procfs to read the processes from, e.g. the host one mounted in a container
+    #[cfg(unix)]
+    #[arg(long, default_value = "/proc")]
+    proc_root: PathBuf,
+    /// A passwd file matching `--proc-root` to resolve the user names
+    #[cfg(unix)]
+    #[arg(long)]
+    passwd: Option<PathBuf>,
+    /// A group file matching `--proc-root` to resolve the group names
+    #[cfg(unix)]
+    #[arg(long)]
+    group: Option<PathBuf>,
+    /// How many threads read `/proc`, 1 reads it sequentially
+    #[cfg(unix)]
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+    /// Only read again the processes that changed since the last acquisition
+    #[cfg(unix)]
+    #[arg(long)]
+    incremental: bool,
+    /// Read the PSS and USS of the processes from `/proc/[pid]/smaps_rollup`, this is slow
+    #[cfg(unix)]
+    #[arg(long)]
+    smaps_rollup: bool,
+    /// Read the threads of the processes from `/proc/[pid]/task`, see `/processes/{pid}/threads`
+    #[cfg(unix)]
+    #[arg(long)]
+    enumerate_threads: bool,
+    /// Read the open file descriptors of the processes, see `/processes/{pid}/fds` and `/files`
+    #[cfg(unix)]
+    #[arg(long)]
+    fds: bool,
+    /// Read the environment of the processes, see the `env=` and `env_flag=` search
+    #[cfg(unix)]
+    #[arg(long)]
+    environ: bool,
+    /// Read the files mapped by the processes, see `/processes/{pid}/libraries` and `/libraries`
+    #[cfg(unix)]
+    #[arg(long)]
+    maps: bool,
+    /// Compute the SHA-256 of the executables of the processes, see the `exe_sha256=` search
+    #[cfg(unix)]
+    #[arg(long)]
+    exe_hash: bool,
+    /// The bigger executables are not hashed
+    #[cfg(unix)]
+    #[arg(long, default_value_t = 256 * 1024 * 1024)]
+    exe_hash_max_size: u64,
+    /// The milliseconds an acquisition can spend hashing executables
+    #[cfg(unix)]
+    #[arg(long, default_value_t = 2000)]
+    exe_hash_budget_ms: u64,
+    /// Compare two snapshots exported from `/snapshots/{id}`, print the diff and exit
+    #[arg(long, num_args = 2, value_names = ["FROM", "TO"])]
+    diff: Option<Vec<PathBuf>>,
+    /// Acquire the processes in the background every that many milliseconds,
+    /// the netlink probe defaults to a scan every minute
+    #[arg(long)]
+    acquire_interval: Option<u64>,
+    /// Add a random delay up to that many milliseconds to each background
+    /// acquisition
+    #[arg(long, default_value_t = 0)]
+    acquire_jitter: u64,
+    /// Minimum milliseconds between two background acquisitions
+    #[arg(long, default_value_t = 1000)]
+    acquire_min_spacing: u64,
+    /// Acquire the processes when receiving SIGUSR1
+    #[arg(long)]
+    acquire_on_sigusr1: bool,
+    /// Acquire the processes when a new subscriber connects to `/data`
+    #[arg(long)]
+    acquire_on_subscribe: bool,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -33,21 +115,24 @@ enum ProbeType {
     Procfs,
     #[cfg(unix)]
     Psutil,
+    #[cfg(unix)]
+    Netlink,
 }
 
-fn parse_config() -> (ManagerConfig, WarpServerConfig) {
-    let cli = Cli::parse();
-    let manager_config = match cli.probe_type {
+fn parse_config(cli: Cli) -> (ManagerConfig, WarpServerConfig) {
+    let mut manager_config = match cli.probe_type {
         ProbeType::Manual => {
             #[cfg(windows)]
                 let config =
                 ManagerConfig {
-                    typ: WindowsProbe::Manual
+                    typ: WindowsProbe::Manual,
+                    ..Default::default()
                 };
             #[cfg(unix)]
                 let config =
                 ManagerConfig {
-                    typ: UnixProbe::Manual
+                    typ: UnixProbe::Manual,
+                    ..Default::default()
                 };
 
 
@@ -56,19 +141,59 @@ fn parse_config() -> (ManagerConfig, WarpServerConfig) {
         #[cfg(windows)]
         ProbeType::Sysinfo =>
             ManagerConfig {
-                typ: WindowsProbe::Sysinfo
+                typ: WindowsProbe::Sysinfo,
+                ..Default::default()
             },
         #[cfg(unix)]
         ProbeType::Procfs =>
             ManagerConfig {
-                typ: UnixProbe::Procfs
+                typ: UnixProbe::Procfs,
+                ..Default::default()
             },
         #[cfg(unix)]
         ProbeType::Psutil =>
             ManagerConfig {
-                typ: UnixProbe::Psutil
+                typ: UnixProbe::Psutil,
+                ..Default::default()
+            },
+        #[cfg(unix)]
+        ProbeType::Netlink =>
+            ManagerConfig {
+                typ: UnixProbe::Netlink,
+                ..Default::default()
             },
     };
+    let dedup = DedupPolicy::default();
+    manager_config.dedup = DedupPolicy {
+        max_age: cli.dedup_max_age.or(dedup.max_age),
+        capacity: cli.dedup_capacity.or(dedup.capacity),
+    };
+    manager_config.history = Some(cli.history);
+    #[cfg(unix)]
+    {
+        manager_config.unix = UnixProbeConfig {
+            proc_root: cli.proc_root,
+            passwd: cli.passwd,
+            group: cli.group,
+            threads: cli.threads,
+            incremental: cli.incremental,
+            smaps_rollup: cli.smaps_rollup,
+            enumerate_threads: cli.enumerate_threads,
+            fds: cli.fds,
+            environ: cli.environ,
+            maps: cli.maps,
+            exe_hash: cli.exe_hash,
+            exe_hash_max_size: cli.exe_hash_max_size,
+            exe_hash_budget_ms: cli.exe_hash_budget_ms,
+        };
+    }
+    manager_config.scheduler = SchedulerConfig {
+        interval_ms: cli.acquire_interval,
+        jitter_ms: cli.acquire_jitter,
+        min_spacing_ms: cli.acquire_min_spacing,
+        on_sigusr1: cli.acquire_on_sigusr1,
+        on_subscribe: cli.acquire_on_subscribe,
+    };
     let address = match IpAddr::from_str(cli.address.as_str()) {
         Ok(x) => { x }
         Err(e) => {
@@ -86,9 +211,25 @@ fn parse_config() -> (ManagerConfig, WarpServerConfig) {
 
 #[tokio::main]
 async fn main() {
-    let (manager_config, server_config) = parse_config();
+    let cli = Cli::parse();
+    if let Some(files) = &cli.diff {
+        match SnapshotDiff::from_files(&files[0], &files[1]) {
+            Ok(diff) => println!(
+                "{}",
+                serde_json::to_string_pretty(&diff).expect("Serializable diff")
+            ),
+            Err(e) => {
+                eprintln!("Could not diff the snapshots {e}");
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+    let (manager_config, server_config) = parse_config(cli);
     tracing_subscriber::fmt().with_max_level(tracing::Level::DEBUG).finish().init();
-    let manager = Manager::new(manager_config);
+    let manager = Arc::new(RwLock::new(Manager::new(manager_config)));
+    Manager::start_scheduler(&manager);
+    Manager::start_event_listener(&manager);
     let server = WarpServer::new(server_config);
-    server.serve(Arc::new(RwLock::new(manager))).await
+    server.serve(manager).await
 }
@@ -1,11 +1,31 @@
+use crate::{
+    process::{AcquisitionOptions, DedupPolicy, DEFAULT_RETENTION},
+    scheduler::{Scheduler, SchedulerConfig},
+};
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
-use tracing::debug;
+use std::sync::Arc;
+use tracing::{debug, trace};
 
 pub struct Manager {
     #[cfg(unix)]
-    process_probe: crate::process::UnixProcessProbe,
+    process_probe:    crate::process::UnixProcessProbe,
     #[cfg(windows)]
-    process_probe: crate::process::WindowsProcessProbe,
+    process_probe:    crate::process::WindowsProcessProbe,
+    scheduler_config: SchedulerConfig,
+    scheduler:        Option<Scheduler>,
+    event_listener:   Option<EventListener>,
+    /// Only one acquisition is read at a time, the others wait for it
+    acquisition:      Arc<tokio::sync::Mutex<()>>,
+}
+
+/// Applies the kernel events of an event driven probe as they arrive
+struct EventListener(tokio::task::JoinHandle<()>);
+
+impl Drop for EventListener {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, Debug)]
@@ -14,15 +34,29 @@ pub enum UnixProbe {
     #[default]
     Manual,
     Psutil,
+    /// Kernel events from the proc connector, see
+    /// [`crate::process::UnixNetlinkProbe`]
+    Netlink,
 }
 
 #[cfg(unix)]
-impl Into<crate::process::UnixProcessProbe> for UnixProbe {
-    fn into(self) -> crate::process::UnixProcessProbe {
+impl UnixProbe {
+    pub fn probe(self, root: crate::process::ProcRoot) -> crate::process::UnixProcessProbe {
+        match self {
+            UnixProbe::Procfs => crate::process::UnixProcfsProbe::new(root).boxed(),
+            UnixProbe::Manual => crate::process::UnixManualProbe::new(root).boxed(),
+            UnixProbe::Psutil => crate::process::UnixPsutilProbe::new(root).boxed(),
+            UnixProbe::Netlink => crate::process::UnixNetlinkProbe::new(root).boxed(),
+        }
+    }
+
+    /// The periodic acquisitions of the probe when none is configured, the
+    /// event driven probes still scan `/proc` from time to time to catch the
+    /// lost events
+    pub fn default_interval_ms(&self) -> Option<u64> {
         match self {
-            UnixProbe::Procfs => crate::process::UnixManualProbe::default().boxed(),
-            UnixProbe::Manual => crate::process::UnixProcfsProbe::default().boxed(),
-            UnixProbe::Psutil => crate::process::UnixPsutilProbe::default().boxed(),
+            UnixProbe::Netlink => Some(crate::process::UnixNetlinkProbe::RECONCILE_INTERVAL_MS),
+            _ => None,
         }
     }
 }
@@ -47,20 +81,118 @@ impl Into<crate::process::WindowsProcessProbe> for WindowsProbe {
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct ManagerConfig {
     #[cfg(unix)]
-    pub typ: UnixProbe,
+    pub typ:       UnixProbe,
     #[cfg(windows)]
-    pub typ: WindowsProbe,
+    pub typ:       WindowsProbe,
+    /// Where the processes are read from
+    #[cfg(unix)]
+    #[serde(default)]
+    pub unix:      crate::process::UnixProbeConfig,
+    /// How the processes already sent on `/data` are forgotten
+    #[serde(default)]
+    pub dedup:     DedupPolicy,
+    /// How many acquisitions are kept in memory, `None` keeps the default
+    #[serde(default)]
+    pub history:   Option<usize>,
+    /// The background acquisitions, see [`Manager::start_scheduler`]
+    ///
+    /// Note: without an interval, the one of the probe is used, see
+    /// [`UnixProbe::default_interval_ms`]
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
 }
 
 impl Manager {
     pub fn new(config: ManagerConfig) -> Self {
         debug!("Running with config {:?}", &config);
-        Self {
+        #[cfg(unix)]
+        let interval_ms = config.scheduler.interval_ms.or_else(|| config.typ.default_interval_ms());
+        #[cfg(windows)]
+        let interval_ms = config.scheduler.interval_ms;
+        let mut manager = Self {
             #[cfg(unix)]
-            process_probe:                 config.typ.into(),
+            process_probe:                 config
+                .typ
+                .probe(crate::process::ProcRoot::new(&config.unix)),
             #[cfg(windows)]
             process_probe:                 config.typ.into(),
+            scheduler_config:              SchedulerConfig {
+                interval_ms,
+                ..config.scheduler
+            },
+            scheduler:                     None,
+            event_listener:                None,
+            acquisition:                   Default::default(),
+        };
+        let cache = manager.process_probe.process_cache_mut();
+        cache.set_dedup_policy(config.dedup);
+        cache.inner_mut().set_retention(config.history.unwrap_or(DEFAULT_RETENTION));
+        manager
+    }
+
+    /// Acquire the processes without blocking the runtime nor the readers, the
+    /// processes are read on a blocking thread without holding the manager
+    /// and the new snapshot is swapped in once complete
+    ///
+    /// Note: this must be called from a tokio runtime
+    pub async fn acquire(
+        manager: &Arc<RwLock<Manager>>,
+        options: AcquisitionOptions,
+    ) -> std::io::Result<usize> {
+        let acquisition = manager.read().acquisition.clone();
+        let _guard = acquisition.lock().await;
+        let collector = manager.read().process_probe.collector(options);
+        let collection = tokio::task::spawn_blocking(collector)
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))??;
+        let count = manager.write().process_probe.publish_processes(collection).len();
+        Ok(count)
+    }
+
+    /// Start the background acquisitions if the configuration enables them,
+    /// the task is owned by the manager and stops with it
+    ///
+    /// Note: this must be called from a tokio runtime
+    pub fn start_scheduler(manager: &Arc<RwLock<Manager>>) {
+        let mut guard = manager.write();
+        if !guard.scheduler_config.is_enabled() {
+            debug!("No background acquisition configured");
+            return;
         }
+        let scheduler = Scheduler::spawn(guard.scheduler_config.clone(), Arc::downgrade(manager));
+        guard.scheduler = Some(scheduler);
+    }
+
+    /// Apply the kernel events of the probe as soon as they are received, does
+    /// nothing if the probe is not event driven
+    ///
+    /// Note: this must be called from a tokio runtime
+    pub fn start_event_listener(manager: &Arc<RwLock<Manager>>) {
+        let mut guard = manager.write();
+        let notify = match guard.process_probe.pending_events() {
+            Some(x) => x,
+            None => {
+                debug!("The probe is not event driven");
+                return;
+            },
+        };
+        let weak = Arc::downgrade(manager);
+        let handle = tokio::spawn(async move {
+            loop {
+                notify.notified().await;
+                let manager = match weak.upgrade() {
+                    Some(x) => x,
+                    None => break,
+                };
+                let applied = manager.write().process_probe.apply_pending_events();
+                trace!("Applied {} kernel events", applied);
+            }
+        });
+        guard.event_listener = Some(EventListener(handle));
+    }
+
+    pub fn scheduler(&self) -> Option<&Scheduler> {
+        self.scheduler.as_ref()
     }
 
     #[cfg(unix)]
@@ -83,3 +215,29 @@ impl Manager {
         &self.process_probe
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Manager, ManagerConfig};
+    use parking_lot::RwLock;
+    use std::sync::Arc;
+
+    #[test_log::test(tokio::test)]
+    async fn test_acquire() {
+        let manager = Arc::new(RwLock::new(Manager::new(ManagerConfig::default())));
+        let acquisitions = (0..2).map(|_| {
+            let manager = manager.clone();
+            tokio::spawn(async move { Manager::acquire(&manager, Default::default()).await })
+        });
+        for acquisition in acquisitions.collect::<Vec<_>>() {
+            assert!(acquisition.await.expect("works").expect("works") > 0);
+        }
+        let manager = manager.read();
+        let cache = manager.process_probe().process_cache().inner();
+        assert_eq!(cache.snapshots().count(), 2);
+        assert_eq!(
+            manager.process_probe().get_cached_processes().len(),
+            cache.current().map(|x| x.processes().len()).unwrap_or_default()
+        );
+    }
+}
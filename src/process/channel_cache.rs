@@ -1,19 +1,189 @@
-use crate::{Cache, ChannelCache, Pid, ProcessCache, StaticProcess};
-use std::collections::HashSet;
+use crate::{
+    changed_fields,
+    Cache,
+    ChannelCache,
+    ProcessCache,
+    ProcessEvent,
+    ProcessIdentity,
+    StaticProcess,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
+/// How the identities already sent on the channels are forgotten
+///
+/// An identity seen during the last acquisition is never evicted, this ensures
+/// a still running process is never sent twice
+///
+/// Note: a process missing from an acquisition is only sent as exited once its
+/// identity is forgotten (or on a kernel exit event), so one that could not be
+/// read for a few acquisitions (permissions, races) is not sent again
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct DedupPolicy {
+    /// Forget the identities that were not seen in the last `max_age`
+    /// acquisitions, `None` means never
+    pub max_age:  Option<u64>,
+    /// Forget the oldest identities once more than `capacity` are remembered,
+    /// `None` means unbounded
+    pub capacity: Option<usize>,
+}
+
+impl Default for DedupPolicy {
+    fn default() -> Self {
+        Self {
+            // a process that could not be read for a few acquisitions (permissions, races)
+            // should not be sent again
+            max_age:  Some(16),
+            capacity: None,
+        }
+    }
+}
+
 pub struct ChannelProcessCache<T: StaticProcess> {
-    channels: Vec<UnboundedSender<T>>,
-    seen:     HashSet<(Pid, Pid)>,
-    cache:    ProcessCache<T>,
+    channels:    Vec<UnboundedSender<ProcessEvent<T>>>,
+    // identity to the last acquisition it was seen in
+    seen:        HashMap<ProcessIdentity, u64>,
+    // the processes of the previous acquisition not yet seen in the current one
+    previous:    HashMap<ProcessIdentity, T>,
+    // the processes missing from the last acquisitions, their exit is sent once their
+    // identity is forgotten
+    missing:     HashMap<ProcessIdentity, T>,
+    policy:      DedupPolicy,
+    acquisition: u64,
+    cache:       ProcessCache<T>,
 }
 
 impl<T: StaticProcess> Default for ChannelProcessCache<T> {
     fn default() -> Self {
         Self {
-            channels: Vec::new(),
-            seen:     Default::default(),
-            cache:    ProcessCache::new(),
+            channels:    Vec::new(),
+            seen:        Default::default(),
+            previous:    Default::default(),
+            missing:     Default::default(),
+            policy:      Default::default(),
+            acquisition: 0,
+            cache:       ProcessCache::default(),
+        }
+    }
+}
+
+impl<T: StaticProcess> ChannelProcessCache<T> {
+    pub fn set_dedup_policy(&mut self, policy: DedupPolicy)
+    where
+        T: Clone,
+    {
+        tracing::debug!("Using dedup policy {:?}", policy);
+        self.policy = policy;
+        self.evict();
+    }
+
+    pub fn dedup_policy(&self) -> DedupPolicy {
+        self.policy
+    }
+
+    /// The generations backing this cache
+    pub fn inner(&self) -> &ProcessCache<T> {
+        &self.cache
+    }
+
+    pub fn inner_mut(&mut self) -> &mut ProcessCache<T> {
+        &mut self.cache
+    }
+
+    /// The number of identities currently remembered to deduplicate
+    pub fn seen_len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Add or refresh a single process outside of an acquisition (for example
+    /// from a kernel event), this sends the same events as an acquisition would
+    pub fn upsert(&mut self, value: T)
+    where
+        T: Clone,
+    {
+        let identity = value.identity();
+        let known = self.seen.insert(identity, self.acquisition).is_some();
+        // a missing process is back, compare it to its last known value
+        let missing = self.missing.remove(&identity);
+        match self.cache.update(value.clone()).or(missing) {
+            Some(before) => {
+                let changes = changed_fields(&before, &value);
+                if !changes.is_empty() {
+                    self.send(ProcessEvent::Change {
+                        process: value,
+                        changes,
+                    });
+                }
+            },
+            None if !known => self.send(ProcessEvent::New(value)),
+            None => {},
+        }
+    }
+
+    /// Remove a single process that exited outside of an acquisition
+    ///
+    /// Note: its identity is still remembered until evicted, so a late read of
+    /// it is not sent as new
+    pub fn remove(&mut self, identity: ProcessIdentity) -> Option<T>
+    where
+        T: Clone,
+    {
+        // it can not be reported as exited by the next acquisition anymore
+        self.previous.remove(&identity);
+        let process = self.cache.remove(identity).or_else(|| self.missing.remove(&identity))?;
+        self.send(ProcessEvent::Exit(process.clone()));
+        Some(process)
+    }
+
+    /// Mark the end of an acquisition, every process of the previous
+    /// acquisition that was not seen again is missing until its identity is
+    /// forgotten, then it is sent as exited
+    pub fn finish_acquisition(&mut self) {
+        self.missing.extend(self.previous.drain());
+    }
+
+    /// Forget the identities according to the policy, this never evicts an
+    /// identity seen in the current acquisition
+    ///
+    /// Note: the missing processes forgotten are sent as exited
+    fn evict(&mut self)
+    where
+        T: Clone,
+    {
+        let current = self.acquisition;
+        if let Some(max_age) = self.policy.max_age {
+            self.seen.retain(|_, last_seen| current - *last_seen < max_age.max(1));
+        }
+        if let Some(capacity) = self.policy.capacity {
+            if self.seen.len() > capacity {
+                let mut evictable = self
+                    .seen
+                    .iter()
+                    .filter(|(_, &last_seen)| last_seen != current)
+                    .map(|(&identity, &last_seen)| (last_seen, identity))
+                    .collect::<Vec<_>>();
+                evictable.sort_unstable();
+                let excess = self.seen.len() - capacity;
+                for (_, identity) in evictable.into_iter().take(excess) {
+                    self.seen.remove(&identity);
+                }
+                if self.seen.len() > capacity {
+                    tracing::debug!(
+                        "Dedup set is over capacity ({} > {}) with running processes only",
+                        self.seen.len(),
+                        capacity
+                    );
+                }
+            }
+        }
+        let mut forgotten =
+            self.missing.keys().filter(|x| !self.seen.contains_key(x)).copied().collect::<Vec<_>>();
+        forgotten.sort_unstable();
+        for identity in forgotten {
+            if let Some(process) = self.missing.remove(&identity) {
+                self.send(ProcessEvent::Exit(process));
+            }
         }
     }
 }
@@ -27,19 +197,35 @@ impl<T: StaticProcess + Clone> Cache<T> for ChannelProcessCache<T> {
     }
 
     fn add(&mut self, value: T) -> &T {
-        // this is not pid rollover safe at all,
-        // we implement a basic check using 2 elements making it very unlikely that this
-        // could collide
-        // Note we never clear that list ever so this will grow in memory ;)
-        if !self.seen.contains(&(value.pid(), value.ppid())) {
-            self.send(value.clone());
+        // the identity is (pid, start time) so this is pid rollover safe, a reused pid
+        // will have a different start time
+        let identity = value.identity();
+        let known = self.seen.insert(identity, self.acquisition).is_some();
+        // a missing process is back, compare it to its last known value
+        let before = self.previous.remove(&identity).or_else(|| self.missing.remove(&identity));
+        if let Some(before) = before {
+            let changes = changed_fields(&before, &value);
+            if !changes.is_empty() {
+                self.send(ProcessEvent::Change {
+                    process: value.clone(),
+                    changes,
+                });
+            }
+        } else if !known {
+            self.send(ProcessEvent::New(value.clone()));
         }
-        self.seen.insert((value.pid(), value.ppid()));
         self.cache.add(value)
     }
 
     fn clear(&mut self) -> Vec<T> {
-        self.cache.clear()
+        // a clear starts a new acquisition, this is the right time to forget the old
+        // identities since the last acquisition is complete
+        self.evict();
+        self.acquisition += 1;
+        let previous = self.cache.clear();
+        self.previous =
+            previous.iter().map(|process| (process.identity(), process.clone())).collect();
+        previous
     }
 
     fn get(&self) -> Vec<&T> {
@@ -47,8 +233,8 @@ impl<T: StaticProcess + Clone> Cache<T> for ChannelProcessCache<T> {
     }
 }
 
-impl<T: StaticProcess + Clone> ChannelCache<T> for ChannelProcessCache<T> {
-    fn subscribe(&mut self) -> UnboundedReceiver<T> {
+impl<T: StaticProcess + Clone> ChannelCache<T, ProcessEvent<T>> for ChannelProcessCache<T> {
+    fn subscribe(&mut self) -> UnboundedReceiver<ProcessEvent<T>> {
         tracing::debug!(
             "Adding another subscriber to the existing {} subscribers",
             self.channels.len()
@@ -58,8 +244,12 @@ impl<T: StaticProcess + Clone> ChannelCache<T> for ChannelProcessCache<T> {
         rx
     }
 
-    fn send(&mut self, value: T) {
-        tracing::debug!("Sending a value to {} channels", self.channels.len());
+    fn send(&mut self, value: ProcessEvent<T>) {
+        tracing::debug!(
+            "Sending a {} event to {} channels",
+            value.name(),
+            self.channels.len()
+        );
         self.channels.retain(|x| x.send(value.clone()).is_ok());
     }
 }
@@ -67,7 +257,7 @@ impl<T: StaticProcess + Clone> ChannelCache<T> for ChannelProcessCache<T> {
 
 macro_rules! impl_channel_process_cache {
     ($probe:ty, $cached:ty, $cache:ty) => {
-        impl $crate::cache::AsChannelCache<$cached> for $probe {
+        impl $crate::cache::AsChannelCache<$cached, $crate::process::ProcessEvent<$cached>> for $probe {
             type Cache = $cache;
 
             fn cache(&self) -> &Self::Cache {
@@ -78,16 +268,79 @@ macro_rules! impl_channel_process_cache {
                 &mut self.cache
             }
         }
-        impl $crate::cache::ChannelCache<$cached> for $probe{
-            fn subscribe(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<$cached>{
-                $crate::cache::AsChannelCache::<$cached>::cache_mut(self).subscribe()
+        impl $crate::cache::ChannelCache<$cached, $crate::process::ProcessEvent<$cached>> for $probe{
+            fn subscribe(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<$crate::process::ProcessEvent<$cached>>{
+                $crate::cache::AsChannelCache::<$cached, $crate::process::ProcessEvent<$cached>>::cache_mut(self).subscribe()
             }
 
-            fn send(&mut self, value: $cached){
-                $crate::cache::AsChannelCache::<$cached>::cache_mut(self).send(value)
+            fn send(&mut self, value: $crate::process::ProcessEvent<$cached>){
+                $crate::cache::AsChannelCache::<$cached, $crate::process::ProcessEvent<$cached>>::cache_mut(self).send(value)
             }
         }
     };
 }
 
 pub(crate) use impl_channel_process_cache;
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cache, ChannelCache, ChannelProcessCache, DedupPolicy, DummyProcess};
+
+    fn acquire(cache: &mut ChannelProcessCache<DummyProcess>, pids: &[u32]) {
+        cache.clear();
+        for &pid in pids {
+            cache.add(DummyProcess::from_manual(pid).expect("works"));
+        }
+        cache.finish_acquisition();
+    }
+
+    #[test_log::test]
+    fn test_eviction_keeps_running() {
+        let mut cache = ChannelProcessCache::<DummyProcess>::default();
+        cache.set_dedup_policy(DedupPolicy {
+            max_age:  Some(2),
+            capacity: Some(1),
+        });
+        let mut rx = cache.subscribe();
+        acquire(&mut cache, &[1, 2]);
+        acquire(&mut cache, &[1]);
+        acquire(&mut cache, &[1]);
+        // 2 was evicted, 1 is still running so it must not be sent again
+        assert_eq!(cache.seen_len(), 1);
+        let mut sent = vec![];
+        while let Ok(event) = rx.try_recv() {
+            sent.push((event.name(), crate::StaticProcess::pid(event.process())));
+        }
+        assert_eq!(sent, vec![("new", 1), ("new", 2), ("exit", 2)]);
+    }
+
+    #[test_log::test]
+    fn test_unreadable_once() {
+        let mut cache = ChannelProcessCache::<DummyProcess>::default();
+        cache.set_dedup_policy(DedupPolicy {
+            max_age:  Some(2),
+            capacity: None,
+        });
+        let mut rx = cache.subscribe();
+        acquire(&mut cache, &[1, 2]);
+        acquire(&mut cache, &[1]);
+        acquire(&mut cache, &[1, 2]);
+        let mut sent = vec![];
+        while let Ok(event) = rx.try_recv() {
+            sent.push((event.name(), crate::StaticProcess::pid(event.process())));
+        }
+        // 2 was missing once, it is neither sent as exited nor as new
+        assert_eq!(sent, vec![("new", 1), ("new", 2)]);
+
+        // 2 is sent as exited once forgotten
+        acquire(&mut cache, &[1]);
+        acquire(&mut cache, &[1]);
+        assert!(rx.try_recv().is_err());
+        acquire(&mut cache, &[1]);
+        let event = rx.try_recv().expect("works");
+        assert_eq!(
+            (event.name(), crate::StaticProcess::pid(event.process())),
+            ("exit", 2)
+        );
+    }
+}
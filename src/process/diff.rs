@@ -0,0 +1,158 @@
+use crate::{
+    changed_fields,
+    FieldChange,
+    ProcessRecord,
+    ProcessServerResult,
+    Snapshot,
+    SnapshotRecord,
+    StaticProcess,
+};
+use serde::Serialize;
+use std::{collections::HashMap, path::Path};
+
+/// A process present in both snapshots with some fields that changed
+#[derive(Serialize, Debug, Clone)]
+pub struct ModifiedProcess {
+    /// The process as seen in the most recent snapshot
+    pub process: ProcessRecord,
+    pub changes: Vec<FieldChange>,
+}
+
+/// The difference between two snapshots, processes are matched by identity
+#[derive(Serialize, Debug, Clone)]
+pub struct SnapshotDiff {
+    pub from:     u64,
+    pub to:       u64,
+    pub started:  Vec<ProcessRecord>,
+    pub exited:   Vec<ProcessRecord>,
+    pub modified: Vec<ModifiedProcess>,
+}
+
+impl SnapshotDiff {
+    /// Compare two lists of processes, `from` is expected to be the oldest
+    ///
+    /// Note: all the lists are sorted by identity so the result is stable
+    pub fn compare<T: StaticProcess, U: StaticProcess>(
+        from_id: u64,
+        from: &[T],
+        to_id: u64,
+        to: &[U],
+    ) -> Self {
+        let mut before = from.iter().map(|x| (x.identity(), x)).collect::<HashMap<_, _>>();
+        let mut started = vec![];
+        let mut modified = vec![];
+        for process in to {
+            match before.remove(&process.identity()) {
+                None => started.push(ProcessRecord::new(process)),
+                Some(previous) => {
+                    let changes = modified_fields(previous, process);
+                    if !changes.is_empty() {
+                        modified.push(ModifiedProcess {
+                            process: ProcessRecord::new(process),
+                            changes,
+                        });
+                    }
+                },
+            }
+        }
+        let mut exited = before.into_values().map(ProcessRecord::new).collect::<Vec<_>>();
+        started.sort_by_key(|x| x.identity());
+        exited.sort_by_key(|x| x.identity());
+        modified.sort_by_key(|x| x.process.identity());
+        Self {
+            from: from_id,
+            to: to_id,
+            started,
+            exited,
+            modified,
+        }
+    }
+
+    pub fn new<T: StaticProcess>(from: &Snapshot<T>, to: &Snapshot<T>) -> Self {
+        Self::compare(from.id(), from.processes(), to.id(), to.processes())
+    }
+
+    /// Compare two exported snapshots (the output of `/snapshots/{id}`)
+    pub fn from_records(from: &SnapshotRecord, to: &SnapshotRecord) -> Self {
+        Self::compare(from.id, &from.processes, to.id, &to.processes)
+    }
+
+    /// Compare two exported snapshots saved as JSON files, this allows to diff
+    /// offline
+    pub fn from_files<P: AsRef<Path>>(from: P, to: P) -> ProcessServerResult<Self> {
+        let from: SnapshotRecord = serde_json::from_reader(std::fs::File::open(from)?)?;
+        let to: SnapshotRecord = serde_json::from_reader(std::fs::File::open(to)?)?;
+        Ok(Self::from_records(&from, &to))
+    }
+}
+
+/// Every field that can change for the same process, this is a superset of the
+/// fields of a change event
+pub fn modified_fields<T: StaticProcess, U: StaticProcess>(
+    before: &T,
+    after: &U,
+) -> Vec<FieldChange> {
+    let mut changes = changed_fields(before, after);
+    let mut compare = |field: &'static str, before: String, after: String| {
+        if before != after {
+            changes.push(FieldChange {
+                field,
+                before,
+                after,
+            });
+        }
+    };
+    compare("name", before.name(), after.name());
+    compare(
+        "username",
+        before.owner_name().to_string_lossy().into_owned(),
+        after.owner_name().to_string_lossy().into_owned(),
+    );
+    compare(
+        "cwd",
+        before.cwd().to_string_lossy().into_owned(),
+        after.cwd().to_string_lossy().into_owned(),
+    );
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ProcessRecord, SnapshotDiff, SnapshotRecord};
+
+    fn record(pid: u32, start_time: u64, cmdline: &str) -> ProcessRecord {
+        ProcessRecord {
+            pid,
+            ppid: 1,
+            start_time,
+            name: "test".to_string(),
+            uid: "0".to_string(),
+            username: "root".to_string(),
+            exe: Default::default(),
+            cwd: Default::default(),
+            cmdline: cmdline.to_string(),
+            args: vec![],
+        }
+    }
+
+    #[test_log::test]
+    fn test_diff() {
+        let from = SnapshotRecord {
+            id:        1,
+            timestamp: 0,
+            processes: vec![record(1, 10, "a"), record(2, 10, "b"), record(3, 10, "c")],
+        };
+        // pid 2 was reused, pid 3 changed its cmdline
+        let to = SnapshotRecord {
+            id:        2,
+            timestamp: 0,
+            processes: vec![record(1, 10, "a"), record(2, 20, "b"), record(3, 10, "d")],
+        };
+        let diff = SnapshotDiff::from_records(&from, &to);
+        assert_eq!(diff.started, vec![record(2, 20, "b")]);
+        assert_eq!(diff.exited, vec![record(2, 10, "b")]);
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].changes[0].field, "cmdline");
+        assert_eq!(diff.modified[0].changes[0].before, "c");
+    }
+}
@@ -15,6 +15,10 @@ impl StaticProcess for DummyProcess {
         Default::default()
     }
 
+    fn start_time(&self) -> u64 {
+        Default::default()
+    }
+
     fn name(&self) -> String {
         Default::default()
     }
@@ -55,11 +59,28 @@ pub type DummyProcessProbe = Box<dyn ProcessProbe<DummyProcess>>;
 macro_rules! impl_dummy_probe {
     ($probe:ty, $method:path) => {
         impl $crate::process::ProcessProbe<$crate::process::DummyProcess> for $probe {
-            fn collect_processes(&mut self) -> std::io::Result<Vec<&$crate::process::DummyProcess>> {
+            fn collector(&self, _options: $crate::process::AcquisitionOptions) -> $crate::process::ProcessCollector<$crate::process::DummyProcess> {
+                Box::new(|| {
+                    Ok($crate::process::Collection {
+                        processes: vec![$method(42).expect("Not failing")],
+                        timing:    $crate::process::CollectionTiming {
+                            threads: 1,
+                            pids: 1,
+                            ..Default::default()
+                        },
+                    })
+                })
+            }
+
+            fn publish_processes(&mut self, collection: $crate::process::Collection<$crate::process::DummyProcess>) -> Vec<&$crate::process::DummyProcess> {
                 use $crate::cache::Cache;
                 self.cache.clear();
-                self.cache.add($method(42).expect("Not failing"));
-                Ok(self.get_cached_processes())
+                for process in collection.processes {
+                    self.cache.add(process);
+                }
+                self.cache.inner_mut().set_timing(collection.timing);
+                self.cache.finish_acquisition();
+                self.get_cached_processes()
             }
 
             fn get_cached_processes(&self) -> Vec<&$crate::process::DummyProcess> {
@@ -67,10 +88,18 @@ macro_rules! impl_dummy_probe {
                 self.cache.get()
             }
 
-            fn obtain_channel(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<$crate::process::DummyProcess>{
+            fn obtain_channel(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<$crate::process::ProcessEvent<$crate::process::DummyProcess>>{
                 use $crate::cache::ChannelCache;
                 self.cache.subscribe()
             }
+
+            fn process_cache(&self) -> &$crate::process::ChannelProcessCache<$crate::process::DummyProcess> {
+                &self.cache
+            }
+
+            fn process_cache_mut(&mut self) -> &mut $crate::process::ChannelProcessCache<$crate::process::DummyProcess> {
+                &mut self.cache
+            }
         }
 
         impl $probe {
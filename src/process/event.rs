@@ -0,0 +1,69 @@
+use crate::StaticProcess;
+use serde::Serialize;
+
+/// The change of a single field of a process between two acquisitions
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field:  &'static str,
+    pub before: String,
+    pub after:  String,
+}
+
+/// What happened to a process between two acquisitions
+#[derive(Debug, Clone)]
+pub enum ProcessEvent<T> {
+    /// The process was never seen before
+    New(T),
+    /// The process was in the previous acquisition but not anymore
+    Exit(T),
+    /// The same process (same identity) had some of its fields changed
+    Change {
+        process: T,
+        changes: Vec<FieldChange>,
+    },
+}
+
+impl<T> ProcessEvent<T> {
+    /// The name of the event, this is used as the SSE `event:` field
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProcessEvent::New(_) => "new",
+            ProcessEvent::Exit(_) => "exit",
+            ProcessEvent::Change { .. } => "change",
+        }
+    }
+
+    pub fn process(&self) -> &T {
+        match self {
+            ProcessEvent::New(process) | ProcessEvent::Exit(process) => process,
+            ProcessEvent::Change { process, .. } => process,
+        }
+    }
+}
+
+/// Compare the fields of two views of the same process that are worth a change
+/// event: the owner, the parent (reparenting), the executable and the cmdline
+pub fn changed_fields<T: StaticProcess, U: StaticProcess>(
+    before: &T,
+    after: &U,
+) -> Vec<FieldChange> {
+    let mut changes = vec![];
+    let mut compare = |field: &'static str, before: String, after: String| {
+        if before != after {
+            changes.push(FieldChange {
+                field,
+                before,
+                after,
+            });
+        }
+    };
+    compare("uid", before.owner_id(), after.owner_id());
+    compare("ppid", before.ppid().to_string(), after.ppid().to_string());
+    compare(
+        "exe",
+        before.exe().to_string_lossy().into_owned(),
+        after.exe().to_string_lossy().into_owned(),
+    );
+    compare("cmdline", before.cmdline(), after.cmdline());
+    changes
+}
@@ -1,6 +1,11 @@
+use std::ffi::OsString;
+
 mod channel_cache;
+mod diff;
 mod dummy;
+mod event;
 mod process_cache;
+mod record;
 mod traits;
 #[cfg(unix)]
 mod unix;
@@ -8,15 +13,42 @@ mod unix;
 mod windows;
 
 pub(crate) use channel_cache::impl_channel_process_cache;
-pub use channel_cache::ChannelProcessCache;
+pub use channel_cache::{ChannelProcessCache, DedupPolicy};
 pub(crate) use process_cache::impl_process_cache;
-pub use process_cache::ProcessCache;
+pub use process_cache::{ProcessCache, Snapshot, DEFAULT_RETENTION};
+pub use record::{ProcessRecord, SnapshotRecord};
 
+pub use diff::{modified_fields, ModifiedProcess, SnapshotDiff};
 pub use dummy::*;
-pub use traits::{ProcessProbe, StaticProcess};
+pub use event::{changed_fields, FieldChange, ProcessEvent};
+pub use traits::{
+    AcquisitionOptions,
+    Collection,
+    CollectionTiming,
+    ProcessCollector,
+    ProcessIdentity,
+    ProcessProbe,
+    StaticProcess,
+};
 #[cfg(unix)]
 pub use unix::*;
 #[cfg(windows)]
 pub use windows::*;
 
 pub type Pid = u32;
+
+/// The string itself when valid UTF-8, its base64 (URL safe) otherwise
+pub(crate) fn map_os_string(s: &OsString) -> String {
+    match s.to_str() {
+        None => {
+            #[cfg(windows)]
+            let buffer = std::os::windows::ffi::OsStrExt::encode_wide(s.as_os_str())
+                .flat_map(|x| [(x >> 8) as u8, x as u8])
+                .collect::<Vec<_>>();
+            #[cfg(unix)]
+            let buffer = std::os::unix::ffi::OsStrExt::as_bytes(s.as_os_str());
+            base64::Engine::encode(&base64::prelude::BASE64_URL_SAFE, buffer)
+        },
+        Some(s) => s.to_string(),
+    }
+}
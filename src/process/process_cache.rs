@@ -1,16 +1,193 @@
-use crate::{Cache, StaticProcess};
+use crate::{
+    process::Pid,
+    Cache,
+    CollectionTiming,
+    ProcessIdentity,
+    ProcessRecord,
+    SnapshotDiff,
+    StaticProcess,
+};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::SystemTime,
+};
+
+/// The default number of generations kept in memory
+pub const DEFAULT_RETENTION: usize = 10;
+
+/// The processes of a single acquisition
+pub struct Snapshot<T> {
+    id:        u64,
+    timestamp: SystemTime,
+    processes: Vec<T>,
+    timing:    Option<CollectionTiming>,
+}
+
+impl<T> Snapshot<T> {
+    /// The generation of this snapshot, generations are numbered from 1 and
+    /// never reused
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// When the acquisition of this snapshot started
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    pub fn processes(&self) -> &[T] {
+        &self.processes
+    }
+
+    /// How long reading this acquisition took, `None` if it was not read by a
+    /// collector
+    pub fn timing(&self) -> Option<CollectionTiming> {
+        self.timing
+    }
+}
 
 pub struct ProcessCache<T: StaticProcess> {
-    inner: Vec<T>,
+    // the processes of the current generation, in the same order as its records
+    processes: Vec<T>,
+    // the position of the processes of the current generation, by identity so the
+    // processes of a pid are found without a scan
+    index:     BTreeMap<ProcessIdentity, usize>,
+    // the generations only keep the records of their processes, the oldest one is at
+    // the front and the current one at the back
+    snapshots: VecDeque<Snapshot<ProcessRecord>>,
+    retention: usize,
+    next_id:   u64,
 }
 
 impl<T: StaticProcess> Default for ProcessCache<T> {
     fn default() -> Self {
-        Self { inner: Vec::new() }
+        Self {
+            processes: Vec::new(),
+            index:     BTreeMap::new(),
+            snapshots: VecDeque::new(),
+            retention: DEFAULT_RETENTION,
+            next_id:   1,
+        }
+    }
+}
+
+impl<T: StaticProcess> ProcessCache<T> {
+    /// Set how many generations are kept (including the current one), at least
+    /// one is always kept
+    pub fn set_retention(&mut self, retention: usize) {
+        tracing::debug!("Keeping {} generations", retention);
+        self.retention = retention.max(1);
+        self.trim();
+    }
+
+    pub fn retention(&self) -> usize {
+        self.retention
+    }
+
+    /// All the generations retained, from the oldest to the current one
+    ///
+    /// Note: the generations keep a record of their processes, the processes
+    /// themselves are only kept for the current one (see `get`)
+    pub fn snapshots(&self) -> impl Iterator<Item = &Snapshot<ProcessRecord>> {
+        self.snapshots.iter()
+    }
+
+    /// Get a retained generation
+    pub fn snapshot(&self, id: u64) -> Option<&Snapshot<ProcessRecord>> {
+        self.snapshots.iter().find(|snapshot| snapshot.id == id)
+    }
+
+    /// Compare two retained generations, `None` if one of them is not retained
+    pub fn diff(&self, from: u64, to: u64) -> Option<SnapshotDiff> {
+        Some(SnapshotDiff::new(self.snapshot(from)?, self.snapshot(to)?))
+    }
+
+    /// The generation currently filled by the acquisitions
+    pub fn current(&self) -> Option<&Snapshot<ProcessRecord>> {
+        self.snapshots.back()
+    }
+
+    /// Record how long reading the current generation took
+    pub fn set_timing(&mut self, timing: CollectionTiming) {
+        if let Some(current) = self.snapshots.back_mut() {
+            current.timing = Some(timing);
+        }
+    }
+
+    /// The processes of the current generation holding a pid, from the oldest
+    /// to the most recent
+    pub fn with_pid(&self, pid: Pid) -> impl Iterator<Item = &T> {
+        self.index.range(ProcessIdentity::of_pid(pid)).map(|(_, &idx)| &self.processes[idx])
+    }
+
+    /// Replace the process with the same identity in the current generation,
+    /// returns the previous value or `None` if it was added
+    pub fn update(&mut self, value: T) -> Option<T> {
+        match self.index.get(&value.identity()) {
+            Some(&idx) => {
+                // SAFETY: the index only holds the processes of the current generation
+                let current = self.snapshots.back_mut().unwrap();
+                current.processes[idx] = ProcessRecord::new(&value);
+                Some(std::mem::replace(&mut self.processes[idx], value))
+            },
+            None => {
+                self.push(value);
+                None
+            },
+        }
+    }
+
+    /// Remove a process from the current generation
+    ///
+    /// Note: the last process takes its place
+    pub fn remove(&mut self, identity: ProcessIdentity) -> Option<T> {
+        let idx = self.index.remove(&identity)?;
+        // SAFETY: the index only holds the processes of the current generation
+        let current = self.snapshots.back_mut().unwrap();
+        current.processes.swap_remove(idx);
+        let process = self.processes.swap_remove(idx);
+        if let Some(moved) = self.processes.get(idx) {
+            self.index.insert(moved.identity(), idx);
+        }
+        Some(process)
+    }
+
+    fn push(&mut self, value: T) -> &T {
+        if self.snapshots.is_empty() {
+            self.start_generation();
+        }
+        // SAFETY: a generation was started above if there was none
+        let current = self.snapshots.back_mut().unwrap();
+        current.processes.push(ProcessRecord::new(&value));
+        self.index.insert(value.identity(), self.processes.len());
+        self.processes.push(value);
+        // SAFETY: an element was inserted above, else this would have panicked on
+        // allocation
+        self.processes.last().unwrap()
+    }
+
+    fn start_generation(&mut self) {
+        tracing::debug!("Starting generation {}", self.next_id);
+        self.snapshots.push_back(Snapshot {
+            id:        self.next_id,
+            timestamp: SystemTime::now(),
+            processes: Vec::new(),
+            timing:    None,
+        });
+        self.next_id += 1;
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.snapshots.len() > self.retention {
+            if let Some(snapshot) = self.snapshots.pop_front() {
+                tracing::debug!("Dropping generation {}", snapshot.id);
+            }
+        }
     }
 }
 
-impl<T: StaticProcess> Cache<T> for ProcessCache<T> {
+impl<T: StaticProcess + Clone> Cache<T> for ProcessCache<T> {
     fn new() -> Self
     where
         Self: Sized,
@@ -20,20 +197,23 @@ impl<T: StaticProcess> Cache<T> for ProcessCache<T> {
 
     fn add(&mut self, value: T) -> &'_ T {
         tracing::debug!("Adding a new process {}", value.pid());
-        self.inner.push(value);
-        // SAFETY: an element was inserted above, else this would have panicked on
-        // allocation
-        self.inner.last().unwrap()
+        self.push(value)
     }
 
+    /// Start a new generation, the previous ones are kept (as records)
+    /// according to the retention
+    ///
+    /// Note: this returns the processes of the previous generation
     fn clear(&mut self) -> Vec<T> {
         tracing::debug!("Clearing cache");
-        self.inner.drain(0..).collect::<Vec<_>>()
+        self.start_generation();
+        self.index.clear();
+        std::mem::take(&mut self.processes)
     }
 
     fn get(&self) -> Vec<&'_ T> {
         tracing::debug!("Obtaining a copy of the cache");
-        self.inner.iter().collect()
+        self.processes.iter().collect()
     }
 }
 
@@ -73,3 +253,59 @@ macro_rules! impl_process_cache {
 }
 
 pub(crate) use impl_process_cache;
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cache, DummyProcess, ProcessCache, ProcessRecord, StaticProcess};
+
+    #[test_log::test]
+    fn test_retention() {
+        let mut cache = ProcessCache::<DummyProcess>::default();
+        cache.set_retention(2);
+        for pid in 1..=3 {
+            // the processes of the previous generation are returned
+            assert_eq!(cache.clear().len(), if pid == 1 { 0 } else { 1 });
+            cache.add(DummyProcess::from_manual(pid).expect("works"));
+        }
+        let ids = cache.snapshots().map(|x| x.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![2, 3]);
+        assert!(cache.snapshot(1).is_none());
+        assert_eq!(cache.snapshot(2).expect("works").processes()[0].pid, 2);
+        assert_eq!(cache.get().len(), 1);
+    }
+
+    #[test_log::test]
+    fn test_update_and_remove() {
+        let record = |pid, start_time| ProcessRecord {
+            start_time,
+            ..ProcessRecord::new(&DummyProcess::from_manual(pid).expect("works"))
+        };
+        let mut cache = ProcessCache::<ProcessRecord>::default();
+        for (pid, start_time) in [(1, 0), (2, 0), (2, 5), (3, 0)] {
+            cache.add(record(pid, start_time));
+        }
+        let start_times = |cache: &ProcessCache<_>, pid| {
+            cache.with_pid(pid).map(|x: &ProcessRecord| x.start_time).collect::<Vec<_>>()
+        };
+        assert_eq!(start_times(&cache, 2), [0, 5]);
+        let renamed = ProcessRecord {
+            name: "renamed".to_string(),
+            ..record(2, 5)
+        };
+        assert!(cache.update(renamed).is_some());
+        assert!(cache.update(record(4, 0)).is_none());
+        // the last process takes the place of the removed one
+        assert!(cache.remove(record(1, 0).identity()).is_some());
+        assert!(cache.remove(record(1, 0).identity()).is_none());
+        assert!(start_times(&cache, 1).is_empty());
+        assert_eq!(start_times(&cache, 4), [0]);
+        assert_eq!(cache.with_pid(2).last().expect("works").name, "renamed");
+        let current = cache.current().expect("works").processes();
+        assert_eq!(
+            current.iter().map(|x| x.pid).collect::<Vec<_>>(),
+            [4, 2, 2, 3]
+        );
+        assert_eq!(current[2].name, "renamed");
+        assert_eq!(cache.get().len(), 4);
+    }
+}
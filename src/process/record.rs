@@ -0,0 +1,104 @@
+use crate::{process::map_os_string, Pid, Snapshot, StaticProcess};
+use serde::{Deserialize, Serialize};
+use std::{ffi::OsString, path::PathBuf, time::UNIX_EPOCH};
+
+/// An owned and serializable copy of all the fields of a process
+///
+/// This is what a snapshot is exported as, so an exported snapshot can be read
+/// back and used as any other process
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProcessRecord {
+    pub pid:        Pid,
+    pub ppid:       Pid,
+    pub start_time: u64,
+    pub name:       String,
+    pub uid:        String,
+    // Note this is lossy (base64 when not valid UTF-8), see `JSONProcess`
+    pub username:   String,
+    pub exe:        PathBuf,
+    pub cwd:        PathBuf,
+    pub cmdline:    String,
+    pub args:       Vec<String>,
+}
+
+impl ProcessRecord {
+    pub fn new<T: StaticProcess>(x: &T) -> Self {
+        Self {
+            pid:        x.pid(),
+            ppid:       x.ppid(),
+            start_time: x.start_time(),
+            name:       x.name(),
+            uid:        x.owner_id(),
+            username:   map_os_string(&x.owner_name()),
+            exe:        x.exe(),
+            cwd:        x.cwd(),
+            cmdline:    x.cmdline(),
+            args:       x.args(),
+        }
+    }
+}
+
+impl StaticProcess for ProcessRecord {
+    fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    fn ppid(&self) -> Pid {
+        self.ppid
+    }
+
+    fn start_time(&self) -> u64 {
+        self.start_time
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn owner_id(&self) -> String {
+        self.uid.clone()
+    }
+
+    fn owner_name(&self) -> OsString {
+        self.username.clone().into()
+    }
+
+    fn cmdline(&self) -> String {
+        self.cmdline.clone()
+    }
+
+    fn args(&self) -> Vec<String> {
+        self.args.clone()
+    }
+
+    fn exe(&self) -> PathBuf {
+        self.exe.clone()
+    }
+
+    fn cwd(&self) -> PathBuf {
+        self.cwd.clone()
+    }
+}
+
+/// An exported generation, this is what `/snapshots/{id}` returns
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SnapshotRecord {
+    pub id:        u64,
+    // milliseconds since the epoch
+    pub timestamp: u64,
+    pub processes: Vec<ProcessRecord>,
+}
+
+impl SnapshotRecord {
+    pub fn new<T: StaticProcess>(snapshot: &Snapshot<T>) -> Self {
+        Self {
+            id:        snapshot.id(),
+            timestamp: snapshot
+                .timestamp()
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_millis() as u64)
+                .unwrap_or_default(),
+            processes: snapshot.processes().iter().map(ProcessRecord::new).collect(),
+        }
+    }
+}
@@ -1,19 +1,81 @@
-use crate::{cache::ChannelCache, process::Pid};
+use crate::{cache::ChannelCache, process::Pid, ChannelProcessCache, ProcessEvent};
 
-use std::{ffi::OsString, path::PathBuf};
+use std::{ffi::OsString, path::PathBuf, sync::Arc};
+
+/// Reads the processes of an acquisition without the probe, see
+/// [`ProcessProbe::collector`]
+pub type ProcessCollector<T> = Box<dyn FnOnce() -> std::io::Result<Collection<T>> + Send>;
+
+/// The processes read by a collector
+pub struct Collection<T> {
+    pub processes: Vec<T>,
+    pub timing:    CollectionTiming,
+}
+
+/// What an acquisition reads beyond the configuration of the probe, `None`
+/// keeps the configured value
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct AcquisitionOptions {
+    /// Read the PSS and USS of the processes (unix only), see
+    /// `ProcRoot::smaps_rollup`
+    pub smaps_rollup: Option<bool>,
+}
+
+/// How long reading an acquisition took, to compare the probes and their
+/// settings
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct CollectionTiming {
+    /// The threads reading the processes
+    pub threads:     usize,
+    /// The pids listed
+    pub pids:        usize,
+    /// The pids that could not be read (exited meanwhile, permissions, ...)
+    pub failed:      usize,
+    /// The processes that did not change and were not read again
+    pub reused:      usize,
+    /// Microseconds spent reading the processes
+    pub duration_us: u64,
+}
 
 /// A trait that unify probe on each OSes
 ///
 /// Note: this was left intentionally very simple as this is not meant as a real
 /// probe If we wanted we would have a function to provide a way to add/remove
 /// new process from a kernel callback process probe
-pub trait ProcessProbe<T: StaticProcess + Clone>: ChannelCache<T> + Sync + Send {
+pub trait ProcessProbe<T: StaticProcess + Clone>:
+    ChannelCache<T, ProcessEvent<T>> + Sync + Send
+{
     /// Collect all processes that can be read with the current permissions
-    fn collect_processes(&mut self) -> std::io::Result<Vec<&'_ T>>;
+    fn collect_processes(&mut self) -> std::io::Result<Vec<&'_ T>> {
+        let collection = (self.collector(Default::default()))()?;
+        Ok(self.publish_processes(collection))
+    }
+    /// Get what reads the processes of an acquisition, it does not borrow the
+    /// probe so the (slow) reading can run without holding it, see
+    /// [`crate::Manager::acquire`]
+    fn collector(&self, options: AcquisitionOptions) -> ProcessCollector<T>;
+    /// Swap the cached processes with the ones read by a collector, this only
+    /// touches memory
+    fn publish_processes(&mut self, collection: Collection<T>) -> Vec<&'_ T>;
     /// Get the current processes acquired
     fn get_cached_processes(&self) -> Vec<&'_ T>;
-    /// Get a stream of the current processes acquired
-    fn obtain_channel(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<T>;
+    /// Get a stream of the events (new, exited and changed processes) of the
+    /// next acquisitions
+    fn obtain_channel(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<ProcessEvent<T>>;
+    /// Get the cache backing this probe
+    fn process_cache(&self) -> &ChannelProcessCache<T>;
+    /// Get the cache backing this probe mutably, mostly to configure it
+    fn process_cache_mut(&mut self) -> &mut ChannelProcessCache<T>;
+    /// Apply the events received since the last call, this is only relevant
+    /// for event driven probes and returns the number of events applied
+    fn apply_pending_events(&mut self) -> usize {
+        0
+    }
+    /// Notified when events are waiting to be applied, `None` when the probe
+    /// only collects on demand
+    fn pending_events(&self) -> Option<Arc<tokio::sync::Notify>> {
+        None
+    }
 }
 
 /// A trait that give a static view to a process
@@ -33,6 +95,14 @@ pub trait StaticProcess {
     ///  * This can be spoofed on Windows
     ///  * On Unix, 0 means no parent (usually init)
     fn ppid(&self) -> Pid;
+    /// The start time of the process in milliseconds since the Unix epoch
+    ///
+    /// On Unix this is derived from the field 22 of `/proc/[pid]/stat` (ticks
+    /// since boot) and `btime` from `/proc/stat`, this is stable for the whole
+    /// life of a process
+    ///
+    /// Note: the precision is the one of the kernel clock ticks (usually 10ms)
+    fn start_time(&self) -> u64;
     /// The name is usually what we refer as argv[0] and usually is the path of
     /// the file executed
     ///
@@ -80,4 +150,33 @@ pub trait StaticProcess {
     ///
     /// Note: this might be empty
     fn cwd(&self) -> PathBuf;
+    /// The identity of the process, unlike the pid alone this is safe against
+    /// pid reuse
+    fn identity(&self) -> ProcessIdentity {
+        ProcessIdentity {
+            pid:        self.pid(),
+            start_time: self.start_time(),
+        }
+    }
+}
+
+/// A stable way to refer to a process across acquisitions
+///
+/// A pid can be reused by the kernel once the process exited, but two processes
+/// can not share the same pid at the same start time
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+pub struct ProcessIdentity {
+    pub pid:        Pid,
+    pub start_time: u64,
+}
+
+impl ProcessIdentity {
+    /// All the identities of a pid, the identities are ordered by pid first so
+    /// this finds the processes of a pid in an ordered collection
+    pub fn of_pid(pid: Pid) -> std::ops::RangeInclusive<Self> {
+        let identity = |start_time| Self { pid, start_time };
+        identity(0)..=identity(u64::MAX)
+    }
 }
@@ -4,6 +4,7 @@ use crate::{
         impl_process_cache,
         unix::UnixProcess,
         UnixManualProbe,
+        UnixNetlinkProbe,
         UnixProcfsProbe,
     },
     ChannelProcessCache,
@@ -20,3 +21,6 @@ impl_channel_process_cache!(UnixProcfsProbe, UnixProcess, UnixProcessCache);
 
 impl_process_cache!(UnixPsutilProbe, UnixProcess, UnixProcessCache);
 impl_channel_process_cache!(UnixPsutilProbe, UnixProcess, UnixProcessCache);
+
+impl_process_cache!(UnixNetlinkProbe, UnixProcess, UnixProcessCache);
+impl_channel_process_cache!(UnixNetlinkProbe, UnixProcess, UnixProcessCache);
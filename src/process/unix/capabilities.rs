@@ -0,0 +1,166 @@
+use crate::{ProcessServerError, ProcessServerResult};
+
+/// The capabilities by bit, see https://man7.org/linux/man-pages/man7/capabilities.7.html
+const NAMES: [&str; 41] = [
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETPCAP",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN",
+    "CAP_NET_RAW",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_MKNOD",
+    "CAP_LEASE",
+    "CAP_AUDIT_WRITE",
+    "CAP_AUDIT_CONTROL",
+    "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MAC_ADMIN",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_AUDIT_READ",
+    "CAP_PERFMON",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+/// The capabilities that are as good as root, or close enough, for a process
+/// holding them
+const DANGEROUS: [&str; 9] = [
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_RAWIO",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_SETUID",
+    "CAP_BPF",
+    "CAP_NET_ADMIN",
+];
+
+/// A capability set as the bitmask of `/proc/[pid]/status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CapabilitySet(pub u64);
+
+impl CapabilitySet {
+    fn parse(value: &str) -> ProcessServerResult<Self> {
+        u64::from_str_radix(value.trim(), 16)
+            .map(Self)
+            .map_err(|e| ProcessServerError::InvalidUnixStat(format!("Invalid capabilities {e}")))
+    }
+
+    /// The names of the capabilities in the set, the ones newer than this code
+    /// are named by their bit (`CAP_41`)
+    pub fn names(&self) -> Vec<String> {
+        (0..64)
+            .filter(|bit| self.0 & (1 << bit) != 0)
+            .map(|bit| match NAMES.get(bit) {
+                Some(name) => name.to_string(),
+                None => format!("CAP_{bit}"),
+            })
+            .collect()
+    }
+
+    /// Whether the set has a capability, by name (`CAP_SYS_ADMIN`) and case
+    /// insensitive, the `CAP_` prefix can be omitted
+    pub fn contains(&self, name: &str) -> bool {
+        let name = name.to_ascii_uppercase();
+        let name = name.strip_prefix("CAP_").unwrap_or(&name);
+        (0..64).any(|bit| {
+            self.0 & (1 << bit) != 0
+                && match NAMES.get(bit) {
+                    Some(x) => x[4..] == *name,
+                    None => bit.to_string() == name,
+                }
+        })
+    }
+
+    /// The dangerous capabilities in the set, see
+    /// [`Capabilities::is_dangerous`]
+    pub fn dangerous(&self) -> Vec<&'static str> {
+        DANGEROUS.iter().copied().filter(|x| self.contains(x)).collect()
+    }
+}
+
+/// The capability sets of a process, see the `Cap*` lines of
+/// `/proc/[pid]/status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    pub inheritable: CapabilitySet,
+    pub permitted:   CapabilitySet,
+    pub effective:   CapabilitySet,
+    pub bounding:    CapabilitySet,
+    /// Since Linux 4.3, empty before
+    pub ambient:     CapabilitySet,
+}
+
+impl Capabilities {
+    pub(crate) fn from_status(status: &str) -> ProcessServerResult<Self> {
+        let set = |key: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+                .map(CapabilitySet::parse)
+                .unwrap_or(Ok(CapabilitySet::default()))
+        };
+        Ok(Self {
+            inheritable: set("CapInh")?,
+            permitted:   set("CapPrm")?,
+            effective:   set("CapEff")?,
+            bounding:    set("CapBnd")?,
+            ambient:     set("CapAmb")?,
+        })
+    }
+
+    /// Whether a process of a non root user holds dangerous effective
+    /// capabilities, this is what a hardening audit looks for
+    pub fn is_dangerous(&self, euid: u32) -> bool {
+        euid != 0 && !self.effective.dangerous().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_from_status() {
+        let status = concat!(
+            "CapInh:\t0000000000000000\n",
+            "CapPrm:\t0000000000203000\n",
+            "CapEff:\t0000000000202000\n",
+            "CapBnd:\t000001ffffffffff\n",
+            "CapAmb:\t0000000000000000\n",
+        );
+        let capabilities = Capabilities::from_status(status).expect("works");
+        assert_eq!(
+            capabilities.effective.names(),
+            ["CAP_NET_RAW", "CAP_SYS_ADMIN"]
+        );
+        assert!(capabilities.permitted.contains("net_admin"));
+        assert!(capabilities.bounding.contains("CAP_CHECKPOINT_RESTORE"));
+        assert!(capabilities.is_dangerous(1000));
+        assert!(!capabilities.is_dangerous(0));
+    }
+}
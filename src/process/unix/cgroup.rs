@@ -0,0 +1,207 @@
+use crate::{
+    process::{unix::ProcRoot, Pid},
+    ProcessServerResult,
+};
+
+const CGROUP: &str = "cgroup";
+
+/// A line of `/proc/[pid]/cgroup`, see https://man7.org/linux/man-pages/man7/cgroups.7.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgroupEntry {
+    /// 0 for the unified (v2) hierarchy
+    pub hierarchy:   u32,
+    /// The v1 controllers (`cpu,cpuacct`, `name=systemd`), empty for v2
+    pub controllers: Vec<String>,
+    pub path:        String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    Docker,
+    Containerd,
+    CriO,
+    Podman,
+    /// A kubernetes container of the cgroupfs driver, the runtime does not
+    /// show in the path
+    Unknown,
+}
+
+impl Runtime {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Runtime::Docker => "docker",
+            Runtime::Containerd => "containerd",
+            Runtime::CriO => "cri-o",
+            Runtime::Podman => "podman",
+            Runtime::Unknown => "unknown",
+        }
+    }
+}
+
+/// The container a process runs in, as told by its cgroup path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    pub runtime: Runtime,
+    /// The full (64 hex digits) id
+    pub id:      String,
+    /// The kubernetes pod, with dashes whatever the cgroup driver
+    pub pod_uid: Option<String>,
+    /// `Guaranteed`, `Burstable` or `BestEffort`
+    pub qos:     Option<&'static str>,
+}
+
+/// The cgroups of a process along what they tell about it
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Cgroup {
+    pub entries:   Vec<CgroupEntry>,
+    pub container: Option<Container>,
+    /// The deepest systemd service or scope, e.g. `nginx.service`
+    pub unit:      Option<String>,
+    /// The deepest systemd slice, e.g. `system.slice`
+    pub slice:     Option<String>,
+}
+
+impl Cgroup {
+    /// Note: with v1 the paths of the hierarchies may differ, the unified one
+    /// is preferred then the systemd one
+    pub(crate) fn parse(content: &str) -> Self {
+        let entries = content
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, ':');
+                let hierarchy = fields.next()?.parse().ok()?;
+                let controllers = fields.next()?;
+                Some(CgroupEntry {
+                    hierarchy,
+                    controllers: controllers
+                        .split(',')
+                        .filter(|x| !x.is_empty())
+                        .map(String::from)
+                        .collect(),
+                    path: fields.next()?.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let path = entries
+            .iter()
+            .find(|x| x.hierarchy == 0)
+            .or_else(|| entries.iter().find(|x| x.controllers.iter().any(|x| x == "name=systemd")))
+            .or_else(|| entries.first())
+            .map(|x| x.path.as_str())
+            .unwrap_or_default();
+        let segments = path.split('/').filter(|x| !x.is_empty()).collect::<Vec<_>>();
+        let deepest = |suffixes: &[&str]| {
+            segments
+                .iter()
+                .rev()
+                .find(|x| suffixes.iter().any(|suffix| x.ends_with(suffix)))
+                .map(|x| x.to_string())
+        };
+        Self {
+            container: container(&segments),
+            unit: deepest(&[".service", ".scope"]),
+            slice: deepest(&[".slice"]),
+            entries,
+        }
+    }
+
+    pub(crate) fn read(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Self> {
+        Ok(Self::parse(&std::fs::read_to_string(
+            root.process_path(pid).join(CGROUP),
+        )?))
+    }
+}
+
+fn is_container_id(x: &str) -> bool {
+    x.len() == 64 && x.bytes().all(|x| x.is_ascii_hexdigit())
+}
+
+/// Find the container in the segments of a cgroup path, either a systemd scope
+/// (`docker-<id>.scope`, `cri-containerd-<id>.scope`, `crio-<id>.scope`,
+/// `libpod-<id>.scope`) or a cgroupfs directory (`/docker/<id>`,
+/// `/kubepods/burstable/pod<uid>/<id>`)
+fn container(segments: &[&str]) -> Option<Container> {
+    const SCOPES: [(&str, Runtime); 4] = [
+        ("docker-", Runtime::Docker),
+        ("cri-containerd-", Runtime::Containerd),
+        ("crio-", Runtime::CriO),
+        ("libpod-", Runtime::Podman),
+    ];
+    let (idx, runtime, id) = segments.iter().enumerate().rev().find_map(|(idx, segment)| {
+        let scope = segment.strip_suffix(".scope").unwrap_or(segment);
+        for (prefix, runtime) in SCOPES {
+            match scope.strip_prefix(prefix) {
+                Some(id) if is_container_id(id) => return Some((idx, runtime, id)),
+                _ => {},
+            }
+        }
+        if !is_container_id(segment) {
+            return None;
+        }
+        let runtime = match idx.checked_sub(1).map(|x| segments[x]) {
+            Some("docker") => Runtime::Docker,
+            Some("libpod_parent") => Runtime::Podman,
+            _ => Runtime::Unknown,
+        };
+        Some((idx, runtime, *segment))
+    })?;
+    let kubepods = segments[..idx].iter().any(|x| x.starts_with("kubepods"));
+    let pod_uid = segments[..idx].iter().rev().find_map(|segment| {
+        // `pod<uid>` with cgroupfs, `kubepods-<qos>-pod<uid with underscores>.slice`
+        // with systemd
+        let pod = segment.strip_suffix(".slice").unwrap_or(segment);
+        let uid = &pod[pod.rfind("pod")? + 3..];
+        (kubepods && uid.len() == 36).then(|| uid.replace('_', "-"))
+    });
+    let qos = kubepods.then(|| {
+        let path = segments[..idx].join("/");
+        if path.contains("besteffort") {
+            "BestEffort"
+        } else if path.contains("burstable") {
+            "Burstable"
+        } else {
+            "Guaranteed"
+        }
+    });
+    Some(Container {
+        runtime,
+        id: id.to_string(),
+        pod_uid,
+        qos,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_parse() {
+        let id = "4f5b6e3c2d1a0f9e8d7c6b5a4f3e2d1c0b9a8f7e6d5c4b3a2f1e0d9c8b7a6f5e";
+        let pod = "kubepods-burstable-pod1b2c3d4e_5f60_7182_93a4_b5c6d7e8f901.slice";
+        let cgroup = Cgroup::parse(&format!(
+            "0::/kubepods.slice/kubepods-burstable.slice/{pod}/cri-containerd-{id}.scope\n"
+        ));
+        let container = cgroup.container.expect("works");
+        assert_eq!(container.runtime, Runtime::Containerd);
+        assert_eq!(container.id, id);
+        assert_eq!(
+            container.pod_uid.as_deref(),
+            Some("1b2c3d4e-5f60-7182-93a4-b5c6d7e8f901")
+        );
+        assert_eq!(container.qos, Some("Burstable"));
+        assert_eq!(cgroup.slice.as_deref(), Some(pod));
+
+        let cgroup = Cgroup::parse(&format!(
+            "12:pids:/docker/{id}\n1:name=systemd:/docker/{id}\n"
+        ));
+        assert_eq!(cgroup.entries.len(), 2);
+        let container = cgroup.container.expect("works");
+        assert_eq!((container.runtime, container.qos), (Runtime::Docker, None));
+
+        let cgroup = Cgroup::parse("0::/system.slice/nginx.service\n");
+        assert_eq!(cgroup.container, None);
+        assert_eq!(cgroup.unit.as_deref(), Some("nginx.service"));
+        assert_eq!(cgroup.slice.as_deref(), Some("system.slice"));
+    }
+}
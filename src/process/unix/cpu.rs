@@ -0,0 +1,90 @@
+use crate::process::unix::ticks_per_second;
+use std::time::Instant;
+
+/// The time a process was scheduled, fields 14 and 15 of `/proc/[pid]/stat`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuTimes {
+    pub user_ticks:   u64,
+    pub system_ticks: u64,
+    /// When the times were read
+    pub sampled_at:   Instant,
+}
+
+impl CpuTimes {
+    pub(crate) fn new(user_ticks: u64, system_ticks: u64) -> Self {
+        Self {
+            user_ticks,
+            system_ticks,
+            sampled_at: Instant::now(),
+        }
+    }
+
+    /// The ticks scheduled in user and system mode
+    pub fn total_ticks(&self) -> u64 {
+        self.user_ticks + self.system_ticks
+    }
+
+    /// The usage between an earlier sample of the same process and this one,
+    /// `None` if no time elapsed
+    pub fn usage_since(&self, before: &CpuTimes, cores: usize) -> Option<CpuUsage> {
+        let elapsed = self.sampled_at.checked_duration_since(before.sampled_at)?.as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        let percent = |after: u64, before: u64| {
+            // Note: the times can not decrease for a process, but we don't trust the kernel
+            // that much
+            let seconds = after.saturating_sub(before) as f64 / ticks_per_second() as f64;
+            100.0 * seconds / elapsed / cores.max(1) as f64
+        };
+        Some(CpuUsage {
+            user:   percent(self.user_ticks, before.user_ticks),
+            system: percent(self.system_ticks, before.system_ticks),
+        })
+    }
+}
+
+/// The share of the whole machine used by a process between two acquisitions,
+/// in percent (100 is all the cores busy)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CpuUsage {
+    pub user:   f64,
+    pub system: f64,
+}
+
+impl CpuUsage {
+    pub fn total(&self) -> f64 {
+        self.user + self.system
+    }
+}
+
+/// The number of cores online, the usage is relative to it
+pub(crate) fn online_cores() -> usize {
+    // SAFETY: sysconf has no precondition, it only reads a system value
+    let cores = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) };
+    if cores <= 0 {
+        1
+    } else {
+        cores as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test_log::test]
+    fn test_usage() {
+        let before = CpuTimes::new(100, 50);
+        let after = CpuTimes {
+            user_ticks:   100 + ticks_per_second(),
+            system_ticks: 50,
+            sampled_at:   before.sampled_at + Duration::from_secs(2),
+        };
+        let usage = after.usage_since(&before, 2).expect("works");
+        assert!((usage.user - 25.0).abs() < 1e-9);
+        assert_eq!(usage.system, 0.0);
+        assert!(before.usage_since(&after, 2).is_none());
+    }
+}
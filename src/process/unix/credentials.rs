@@ -0,0 +1,149 @@
+use crate::{process::unix::ProcRoot, ProcessServerError, ProcessServerResult};
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+};
+
+/// The real, effective, saved and filesystem ids of a process, see
+/// https://man7.org/linux/man-pages/man7/credentials.7.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IdSet {
+    pub real:      u32,
+    pub effective: u32,
+    pub saved:     u32,
+    pub fs:        u32,
+}
+
+impl IdSet {
+    /// Parse the value of the `Uid:` or `Gid:` lines of `/proc/[pid]/status`
+    pub(crate) fn parse(value: &str) -> ProcessServerResult<Self> {
+        let ids = value
+            .split_whitespace()
+            .map(|x| x.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ProcessServerError::InvalidUnixStat(format!("Invalid id {e}")))?;
+        match ids[..] {
+            [real, effective, saved, fs] => Ok(Self {
+                real,
+                effective,
+                saved,
+                fs,
+            }),
+            _ => Err(ProcessServerError::InvalidUnixStat(format!(
+                "Expected 4 ids, got {}",
+                ids.len()
+            ))),
+        }
+    }
+
+    pub fn ids(&self) -> [u32; 4] {
+        [self.real, self.effective, self.saved, self.fs]
+    }
+
+    /// Whether the process can act as another id than the real one, this is
+    /// what a setuid (or setgid) binary looks like
+    pub fn is_changed(&self) -> bool {
+        self.ids().iter().any(|x| *x != self.real)
+    }
+}
+
+/// The ids of a process along with their names
+///
+/// Note: the names are resolved when the process is read, an id without a user
+/// (resp. group) has no name
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Credentials {
+    uids:        IdSet,
+    gids:        IdSet,
+    groups:      Vec<u32>,
+    user_names:  BTreeMap<u32, OsString>,
+    group_names: BTreeMap<u32, OsString>,
+}
+
+impl Credentials {
+    pub(crate) fn new(root: &ProcRoot, uids: IdSet, gids: IdSet, groups: Vec<u32>) -> Self {
+        let user_names = uids
+            .ids()
+            .iter()
+            .filter_map(|&uid| root.user_name(uid).ok().map(|name| (uid, name)))
+            .collect();
+        let group_names = gids
+            .ids()
+            .iter()
+            .chain(groups.iter())
+            .filter_map(|&gid| root.group_name(gid).map(|name| (gid, name)))
+            .collect();
+        Self {
+            uids,
+            gids,
+            groups,
+            user_names,
+            group_names,
+        }
+    }
+
+    /// Read the `Uid:`, `Gid:` and `Groups:` lines of `/proc/[pid]/status`
+    pub(crate) fn from_status(root: &ProcRoot, status: &str) -> ProcessServerResult<Self> {
+        let line = |key: &str| {
+            status.lines().find_map(|line| {
+                line.split_once(':').filter(|(name, _)| *name == key).map(|(_, value)| value)
+            })
+        };
+        let uids = IdSet::parse(line("Uid").ok_or(ProcessServerError::InvalidUnixStat(
+            "Missing uid map in /proc/[PID]/status".to_string(),
+        ))?)?;
+        let gids = IdSet::parse(line("Gid").ok_or(ProcessServerError::InvalidUnixStat(
+            "Missing gid map in /proc/[PID]/status".to_string(),
+        ))?)?;
+        // Note: the groups line is empty for kernel threads
+        let groups = line("Groups")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|x| x.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ProcessServerError::InvalidUnixStat(format!("Invalid group {e}")))?;
+        Ok(Self::new(root, uids, gids, groups))
+    }
+
+    pub fn uids(&self) -> IdSet {
+        self.uids
+    }
+
+    pub fn gids(&self) -> IdSet {
+        self.gids
+    }
+
+    /// The supplementary groups
+    pub fn groups(&self) -> &[u32] {
+        &self.groups
+    }
+
+    pub fn user_name(&self, uid: u32) -> Option<&OsStr> {
+        self.user_names.get(&uid).map(|x| x.as_os_str())
+    }
+
+    pub fn group_name(&self, gid: u32) -> Option<&OsStr> {
+        self.group_names.get(&gid).map(|x| x.as_os_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_from_status() {
+        let status = concat!(
+            "Name:\tpasswd\n",
+            "Uid:\t1000\t0\t0\t0\n",
+            "Gid:\t1000\t1000\t1000\t1000\n",
+            "Groups:\t4 27 1000 \n",
+        );
+        let credentials = Credentials::from_status(&ProcRoot::default(), status).expect("works");
+        assert_eq!(credentials.uids().ids(), [1000, 0, 0, 0]);
+        assert!(credentials.uids().is_changed());
+        assert!(!credentials.gids().is_changed());
+        assert_eq!(credentials.groups(), &[4, 27, 1000]);
+        assert_eq!(credentials.user_name(0), Some(OsStr::new("root")));
+    }
+}
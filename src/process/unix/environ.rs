@@ -0,0 +1,105 @@
+use crate::{
+    process::{unix::ProcRoot, Pid},
+    ProcessServerResult,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    os::unix::ffi::OsStrExt,
+};
+
+const ENVIRON: &str = "environ";
+const PROXY_VARIABLES: [&str; 4] = ["http_proxy", "https_proxy", "ftp_proxy", "all_proxy"];
+
+/// The environment of a process as it was when it executed, see
+/// `/proc/[pid]/environ` in https://man7.org/linux/man-pages/man5/proc.5.html
+///
+/// Note: the variables are kept as bytes, nothing forces them to be UTF-8. The
+/// changes made by the process itself (`setenv`) are usually not visible
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Environ {
+    variables: Vec<(OsString, OsString)>,
+}
+
+impl Environ {
+    /// Parse the nul separated `NAME=VALUE` entries, an entry without `=` is
+    /// kept with an empty value
+    pub(crate) fn parse(content: &[u8]) -> Self {
+        let variables = content
+            .split(|x| *x == 0)
+            .filter(|x| !x.is_empty())
+            .map(|entry| {
+                let (name, value) = match entry.iter().position(|x| *x == b'=') {
+                    Some(idx) => (&entry[..idx], &entry[idx + 1..]),
+                    None => (entry, &[][..]),
+                };
+                (
+                    OsStr::from_bytes(name).to_owned(),
+                    OsStr::from_bytes(value).to_owned(),
+                )
+            })
+            .collect();
+        Self { variables }
+    }
+
+    pub(crate) fn read(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Self> {
+        Ok(Self::parse(&std::fs::read(
+            root.process_path(pid).join(ENVIRON),
+        )?))
+    }
+
+    /// The variables in the order of the environment
+    pub fn variables(&self) -> &[(OsString, OsString)] {
+        &self.variables
+    }
+
+    /// The value of a variable, the first one if it is set twice
+    pub fn get(&self, name: impl AsRef<OsStr>) -> Option<&OsStr> {
+        let name = name.as_ref();
+        self.variables.iter().find(|(x, _)| x == name).map(|(_, value)| value.as_os_str())
+    }
+
+    /// Libraries are injected in the process
+    pub fn has_ld_preload(&self) -> bool {
+        self.get("LD_PRELOAD").map(|x| !x.is_empty()).unwrap_or(false)
+    }
+
+    /// The libraries may not be the ones of the system
+    pub fn has_ld_library_path(&self) -> bool {
+        self.get("LD_LIBRARY_PATH").map(|x| !x.is_empty()).unwrap_or(false)
+    }
+
+    /// The connections may go through a proxy, the variables are checked in
+    /// any case as the tools disagree on it
+    pub fn has_proxy(&self) -> bool {
+        self.variables.iter().any(|(name, value)| {
+            !value.is_empty()
+                && PROXY_VARIABLES
+                    .iter()
+                    .any(|x| name.as_bytes().eq_ignore_ascii_case(x.as_bytes()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_parse() {
+        let content = [
+            &b"PATH=/bin"[..],
+            b"LD_PRELOAD=/tmp/x.so",
+            b"HTTPS_PROXY=",
+            b"Https_Proxy=h:3128",
+            b"BIN=\xff\xfe=",
+        ]
+        .join(&0);
+        let environ = Environ::parse(&content);
+        assert_eq!(environ.variables().len(), 5);
+        assert_eq!(environ.get("PATH"), Some(OsStr::new("/bin")));
+        assert_eq!(environ.get("BIN"), Some(OsStr::from_bytes(b"\xff\xfe=")));
+        assert!(environ.has_ld_preload());
+        assert!(!environ.has_ld_library_path());
+        assert!(environ.has_proxy());
+    }
+}
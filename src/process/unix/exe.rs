@@ -0,0 +1,74 @@
+use crate::process::{unix::ProcRoot, Pid};
+use std::{
+    ffi::OsStr,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+const EXE: &str = "exe";
+const ROOT: &str = "root";
+const DELETED_SUFFIX: &[u8] = b" (deleted)";
+const MEMFD_PREFIX: &[u8] = b"/memfd:";
+
+/// What became of the executable of a process since it was executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExeStatus {
+    Present,
+    /// Removed without another file at its path
+    Deleted,
+    /// A `memfd_create` file, it never had a path
+    Memfd,
+    /// Another file is at its path now, e.g. after a package upgrade
+    Replaced,
+    /// The link can't be read, e.g. for the processes of other users or the
+    /// kernel threads
+    Unreadable,
+}
+
+impl ExeStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExeStatus::Present => "present",
+            ExeStatus::Deleted => "deleted",
+            ExeStatus::Memfd => "memfd",
+            ExeStatus::Replaced => "replaced",
+            ExeStatus::Unreadable => "unreadable",
+        }
+    }
+}
+
+/// Read the `/proc/[pid]/exe` link as is (with the ` (deleted)` suffix) and
+/// the status of the executable, empty when unreadable
+///
+/// The file now at the path is looked up through `/proc/[pid]/root` so the
+/// processes of a container are compared to their own filesystem
+///
+/// Note: all the unix probes use this so they agree on the status, as with the
+/// fds a file really named `x (deleted)` is seen as deleted
+pub(crate) fn read_exe(root: &ProcRoot, pid: Pid) -> (PathBuf, ExeStatus) {
+    let process_path = root.process_path(pid);
+    let exe = match process_path.join(EXE).read_link() {
+        Ok(exe) => exe,
+        Err(e) => {
+            debug!("Could not read the exe of {pid} {e}");
+            return (PathBuf::new(), ExeStatus::Unreadable);
+        },
+    };
+    let bytes = exe.as_os_str().as_bytes();
+    if bytes.starts_with(MEMFD_PREFIX) {
+        return (exe, ExeStatus::Memfd);
+    }
+    let Some(path) = bytes.strip_suffix(DELETED_SUFFIX) else {
+        return (exe, ExeStatus::Present);
+    };
+    let path = Path::new(OsStr::from_bytes(path));
+    let inode = |path: &Path| std::fs::metadata(path).map(|x| (x.dev(), x.ino())).ok();
+    let running = inode(&process_path.join(EXE));
+    let current = inode(&process_path.join(ROOT).join(path.strip_prefix("/").unwrap_or(path)));
+    let status = match (running, current) {
+        (Some(running), Some(current)) if running != current => ExeStatus::Replaced,
+        _ => ExeStatus::Deleted,
+    };
+    (exe, status)
+}
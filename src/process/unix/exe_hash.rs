@@ -0,0 +1,128 @@
+use crate::{
+    process::{unix::ProcRoot, Pid},
+    ProcessServerResult,
+};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::File,
+    os::unix::fs::MetadataExt,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+const EXE: &str = "exe";
+
+/// The version of an executable file, the same key is the same content unless
+/// the file is rewritten in place keeping its mtime and size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ExeKey {
+    dev:   u64,
+    inode: u64,
+    mtime: (i64, i64),
+    size:  u64,
+}
+
+/// The SHA-256 of the executables, shared by all the collectors of a root
+///
+/// Note: the entries are never removed, there is one per version of an
+/// executable ever run
+#[derive(Debug)]
+pub(crate) struct ExeHashes {
+    // a cell per key so the callers hashing the same file wait for a single read
+    hashes:   Mutex<HashMap<ExeKey, Arc<OnceCell<[u8; 32]>>>>,
+    max_size: u64,
+    budget:   Duration,
+}
+
+impl ExeHashes {
+    pub(crate) fn new(max_size: u64, budget: Duration) -> Self {
+        Self {
+            hashes: Default::default(),
+            max_size,
+            budget,
+        }
+    }
+
+    /// The time an acquisition can spend hashing
+    pub(crate) fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// The hash of the executable of a process, through `/proc/[pid]/exe` so
+    /// this is the file that is running even if it was removed or replaced
+    ///
+    /// `None` when it is too big, when the `deadline` is past (it is hashed by
+    /// a next acquisition) or when it can't be read, e.g. for the kernel
+    /// threads
+    pub(crate) fn hash(
+        &self,
+        root: &ProcRoot,
+        pid: Pid,
+        deadline: Option<Instant>,
+    ) -> Option<[u8; 32]> {
+        let path = root.process_path(pid).join(EXE);
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                debug!("Could not stat the exe of {pid} {e}");
+                return None;
+            },
+        };
+        let key = ExeKey {
+            dev:   metadata.dev(),
+            inode: metadata.ino(),
+            mtime: (metadata.mtime(), metadata.mtime_nsec()),
+            size:  metadata.size(),
+        };
+        if key.size > self.max_size {
+            debug!(
+                "The exe of {pid} is too big to be hashed ({} bytes)",
+                key.size
+            );
+            return None;
+        }
+        let cell = self.hashes.lock().entry(key).or_default().clone();
+        // nothing is kept on a failure, a next caller tries again
+        let hash = cell.get_or_try_init(|| {
+            if deadline.map(|x| Instant::now() > x).unwrap_or(false) {
+                debug!("No time left to hash the exe of {pid}");
+                return Err(());
+            }
+            hash_file(&path).map_err(|e| debug!("Could not hash the exe of {pid} {e}"))
+        });
+        hash.ok().copied()
+    }
+}
+
+fn hash_file(path: &Path) -> ProcessServerResult<[u8; 32]> {
+    let mut sha256 = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut sha256)?;
+    Ok(sha256.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_hash() {
+        let mut command = std::process::Command::new("sleep").arg("10").spawn().expect("works");
+        let (root, pid) = (ProcRoot::default(), command.id());
+        let hashes = ExeHashes::new(u64::MAX, Duration::from_secs(60));
+        // the budget is spent
+        assert_eq!(hashes.hash(&root, pid, Some(Instant::now())), None);
+        let hash = hashes.hash(&root, pid, None);
+        assert!(hash.is_some());
+        assert_eq!(hashes.hash(&root, pid, Some(Instant::now())), hash);
+        assert_eq!(hashes.hashes.lock().len(), 1);
+        let hashes = ExeHashes::new(0, Duration::from_secs(60));
+        assert_eq!(hashes.hash(&root, pid, None), None);
+        command.kill().expect("works");
+        command.wait().expect("works");
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{
+    process::{unix::ProcRoot, Pid},
+    ProcessServerResult,
+};
+use std::{
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+const FD: &str = "fd";
+const FDINFO: &str = "fdinfo";
+const DELETED_SUFFIX: &[u8] = b" (deleted)";
+
+/// What an open file descriptor points to, see the `/proc/[pid]/fd` section
+/// of https://man7.org/linux/man-pages/man5/proc.5.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdKind {
+    File,
+    /// `socket:[inode]`
+    Socket(u64),
+    /// `pipe:[inode]`
+    Pipe(u64),
+    /// `anon_inode:[eventfd]`, `anon_inode:inotify`, ...
+    AnonInode,
+    Other,
+}
+
+/// An open file descriptor of a process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fd {
+    pub fd:      u32,
+    /// The target of the `/proc/[pid]/fd/[fd]` link as read
+    pub target:  PathBuf,
+    pub kind:    FdKind,
+    /// The file was removed (or replaced) while open, the disk space is only
+    /// freed once it is closed
+    pub deleted: bool,
+    /// The `open` flags of `/proc/[pid]/fdinfo/[fd]`, `None` when it could not
+    /// be read
+    pub flags:   Option<u32>,
+    /// The file offset
+    pub pos:     Option<u64>,
+}
+
+impl Fd {
+    fn new(fd: u32, target: PathBuf) -> Self {
+        let bytes = target.as_os_str().as_bytes();
+        let inode = |prefix: &str| {
+            let value = std::str::from_utf8(bytes.strip_prefix(prefix.as_bytes())?).ok()?;
+            value.strip_prefix('[')?.strip_suffix(']')?.parse().ok()
+        };
+        let kind = if bytes.starts_with(b"/") {
+            FdKind::File
+        } else if let Some(inode) = inode("socket:") {
+            FdKind::Socket(inode)
+        } else if let Some(inode) = inode("pipe:") {
+            FdKind::Pipe(inode)
+        } else if bytes.starts_with(b"anon_inode:") {
+            FdKind::AnonInode
+        } else {
+            FdKind::Other
+        };
+        Self {
+            fd,
+            deleted: kind == FdKind::File && bytes.ends_with(DELETED_SUFFIX),
+            target,
+            kind,
+            flags: None,
+            pos: None,
+        }
+    }
+
+    /// The path of the file without the ` (deleted)` suffix of the removed
+    /// files
+    ///
+    /// Note: a file really named `x (deleted)` can't be told apart from a
+    /// removed `x`, the `deleted` flag is only a hint in that case
+    pub fn path(&self) -> &Path {
+        let bytes = self.target.as_os_str().as_bytes();
+        match bytes.strip_suffix(DELETED_SUFFIX) {
+            Some(path) if self.deleted => Path::new(OsStr::from_bytes(path)),
+            _ => &self.target,
+        }
+    }
+
+    /// Parse the `pos:` and `flags:` lines of `/proc/[pid]/fdinfo/[fd]`, the
+    /// flags are in octal
+    fn parse_fdinfo(&mut self, content: &str) {
+        for line in content.lines() {
+            match line.split_once(':') {
+                Some(("pos", value)) => self.pos = value.trim().parse().ok(),
+                Some(("flags", value)) => self.flags = u32::from_str_radix(value.trim(), 8).ok(),
+                _ => {},
+            }
+        }
+    }
+}
+
+/// Read the open file descriptors of a process, ordered by fd
+///
+/// Note: reading the fds of a process needs the right to trace it, the fds
+/// closed while being read are skipped
+pub(crate) fn read_fds(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Vec<Fd>> {
+    let process_path = root.process_path(pid);
+    let mut fds = std::fs::read_dir(process_path.join(FD))?
+        .filter_map(|entry| entry.ok()?.file_name().to_string_lossy().parse::<u32>().ok())
+        .collect::<Vec<_>>();
+    fds.sort_unstable();
+    Ok(fds
+        .into_iter()
+        .filter_map(|fd| {
+            let target = match process_path.join(FD).join(fd.to_string()).read_link() {
+                Ok(target) => target,
+                Err(e) => {
+                    debug!("Could not read fd {fd} of {pid} {e}");
+                    return None;
+                },
+            };
+            let mut fd = Fd::new(fd, target);
+            if let Ok(content) =
+                std::fs::read_to_string(process_path.join(FDINFO).join(fd.fd.to_string()))
+            {
+                fd.parse_fdinfo(&content);
+            }
+            Some(fd)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_fd_kind() {
+        let fd = Fd::new(3, PathBuf::from("/var/log/app.log (deleted)"));
+        assert_eq!(fd.kind, FdKind::File);
+        assert!(fd.deleted);
+        assert_eq!(fd.path(), Path::new("/var/log/app.log"));
+        assert_eq!(
+            Fd::new(4, PathBuf::from("socket:[1234]")).kind,
+            FdKind::Socket(1234)
+        );
+        assert_eq!(
+            Fd::new(5, PathBuf::from("pipe:[99]")).kind,
+            FdKind::Pipe(99)
+        );
+        assert_eq!(
+            Fd::new(6, PathBuf::from("anon_inode:[eventfd]")).kind,
+            FdKind::AnonInode
+        );
+
+        let mut fd = Fd::new(7, PathBuf::from("/dev/null"));
+        fd.parse_fdinfo("pos:\t12\nflags:\t0100002\nmnt_id:\t25\n");
+        assert_eq!(fd.pos, Some(12));
+        assert_eq!(fd.flags, Some(0o100002));
+    }
+
+    #[test_log::test]
+    fn test_read_deleted() {
+        let path = std::env::temp_dir().join(format!("process_server_fds_{}", std::process::id()));
+        let file = std::fs::File::create(&path).expect("works");
+        std::fs::remove_file(&path).expect("works");
+        let fds = read_fds(&ProcRoot::default(), std::process::id()).expect("works");
+        let fd = fds.iter().find(|x| x.path() == path).expect("works");
+        assert!(fd.deleted);
+        assert!(fd.flags.is_some());
+        drop(file);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    process::{
+        unix::{CpuTimes, ProcRoot},
+        Pid,
+    },
+    ProcessServerError,
+    ProcessServerResult,
+};
+use std::{os::unix::fs::MetadataExt, path::PathBuf};
+
+/// What is cheap to read about a process and enough to tell it did not change
+/// since it was fully read, see [`ProcRoot::incremental`]
+///
+/// Note: the code and arguments addresses of `/proc/[pid]/stat` are new on
+/// each exec (this is the exec generation), but they read as 0 when we are not
+/// allowed to trace the process, then only the comm tells an exec happened.
+/// The arguments are compared as well since they can be rewritten in place
+/// (e.g. `setproctitle`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Fingerprint {
+    comm:        String,
+    ppid:        Pid,
+    start_ticks: u64,
+    // startcode, endcode, arg_start and arg_end
+    exec:        [u64; 4],
+    // the owner of `/proc/[pid]`, it follows the effective uid and gid of the process
+    owner:       (u32, u32),
+    cwd:         Option<PathBuf>,
+    // the raw `/proc/[pid]/cmdline`
+    cmdline:     Vec<u8>,
+}
+
+impl Fingerprint {
+    /// Note: the CPU times are read along as they are in the same file
+    pub(crate) fn read(root: &ProcRoot, pid: Pid) -> ProcessServerResult<(Self, CpuTimes)> {
+        let process_path = root.process_path(pid);
+        let metadata = process_path.metadata()?;
+        let owner = (metadata.uid(), metadata.gid());
+        // See definition in https://man7.org/linux/man-pages/man5/proc.5.html at `/proc/[pid]/stat`
+        let stat = std::fs::read_to_string(process_path.join("stat"))?;
+        let (start, end) = stat.find('(').zip(stat.rfind(')')).ok_or(
+            ProcessServerError::InvalidUnixStat("Could not find comm".to_string()),
+        )?;
+        let comm = stat[start + 1..end].to_string();
+        // fields[0] is state (field 3)
+        let fields = stat[end + 1..].split_whitespace().collect::<Vec<_>>();
+        let field = |idx: usize| -> ProcessServerResult<u64> {
+            fields.get(idx).and_then(|x| x.parse().ok()).ok_or(ProcessServerError::InvalidUnixStat(
+                format!("Invalid field {}", idx + 3),
+            ))
+        };
+        let cpu_times = CpuTimes::new(field(11)?, field(12)?);
+        let fingerprint = Self {
+            comm,
+            ppid: field(1)? as Pid,
+            start_ticks: field(19)?,
+            // the arguments addresses were added in Linux 3.5
+            exec: [
+                field(23)?,
+                field(24)?,
+                field(45).unwrap_or(0),
+                field(46).unwrap_or(0),
+            ],
+            owner,
+            cwd: process_path.join("cwd").read_link().ok(),
+            cmdline: std::fs::read(process_path.join("cmdline"))?,
+        };
+        Ok((fingerprint, cpu_times))
+    }
+}
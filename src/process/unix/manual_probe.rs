@@ -1,13 +1,30 @@
-use std::collections::HashMap;
 
-use std::{ffi::OsStr, fs::File, io::Read, path::PathBuf};
+use std::{fs::File, io::Read, path::PathBuf};
 
 use crate::{
     impl_unix_probe,
     process::{
-        unix::{UnixProcess, UnixProcessCache},
+        unix::{
+            read_cgroup,
+            read_environ_if_enabled,
+            read_exe,
+            read_exe_hash_if_enabled,
+            read_fds_if_enabled,
+            read_lsm_label,
+            read_mappings_if_enabled,
+            read_namespaces,
+            read_threads_if_enumerated,
+            start_time_millis,
+            Capabilities,
+            CpuTimes,
+            Credentials,
+            Memory,
+            ProcRoot,
+            Security,
+            UnixProcess,
+            UnixProcessCache,
+        },
         Pid,
-        PROC_PATH,
     },
     ProcessServerError,
     ProcessServerResult,
@@ -19,12 +36,15 @@ const STAT: &str = "stat";
 const STATUS: &str = "status";
 const CMD_LINE: &str = "cmdline";
 const CWD: &str = "cwd";
-const EXE: &str = "exe";
 
 impl UnixProcess {
     pub fn from_manual(pid: Pid) -> ProcessServerResult<Self> {
+        Self::from_manual_at(&ProcRoot::default(), pid)
+    }
+
+    pub fn from_manual_at(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Self> {
         debug!("Calling manual for pid {}", pid);
-        let process_path = PathBuf::from(PROC_PATH).join(pid.to_string());
+        let process_path = root.process_path(pid);
 
         // See definition in https://man7.org/linux/man-pages/man5/proc.5.html at `/proc/[pid]/stat`
         let stat_path = process_path.join(STAT);
@@ -63,26 +83,8 @@ impl UnixProcess {
         // See definition in https://man7.org/linux/man-pages/man5/proc.5.html at `/proc/[pid]/exe`
         // Note as this is a symlink, you might not have permissions, in that case we
         // return it as empty (this is a deliberate choice for static process)
-        // Note: if the string (deleted) appear as a suffix, it is removed to ensure a
-        // full valid path Note: we suppose linux 2.2 and later (supporting
-        // earlier makes no sense in Rust, since 1.63.0- => Linux 2.6.32+)
-        let exe_path = process_path.join(EXE);
-        let exe = match exe_path.read_link() {
-            Ok(mut e) => {
-                const DELETED_PATTERN: &str = " (deleted)";
-                if e.ends_with(DELETED_PATTERN) {
-                    use std::os::unix::ffi::OsStrExt;
-                    let r = e.as_os_str().as_bytes();
-                    let r = &r[..r.len() - DELETED_PATTERN.len()];
-                    e = PathBuf::from(OsStr::from_bytes(r));
-                }
-                e
-            },
-            Err(e) => {
-                debug!("Could not obtain exe {e}");
-                PathBuf::new()
-            },
-        };
+        // Note: the ` (deleted)` suffix is kept, the status tells what it means
+        let (exe, exe_status) = read_exe(root, pid);
 
         let Some(idx) = stat_content.find('(') else {
             return Err(ProcessServerError::InvalidUnixStat("Could not find comm start".to_string()));
@@ -100,52 +102,77 @@ impl UnixProcess {
         let ppid = fields[1].parse().map_err(|x| {
             ProcessServerError::InvalidUnixStat(format!("The ppid field is not u32 size: {}", x))
         })?;
-
-        // Note: as defined, this is supposed to be a human readable file, so some
-        // spaces needs to be removed sadly this is the only easy place to get
-        // the UID informations Note: we shouldn't error if one of the non
-        // interesting field has a wrong format but for simplification we do
-        let status = status_content
-            .lines()
-            .map(|line| line.split_once(':').map(|(key, value)| (key, value.trim_start())))
-            .collect::<Option<HashMap<_, _>>>()
+        // fields[19] is starttime (field 22), the time the process started after boot
+        // in clock ticks
+        let start_ticks = fields
+            .get(19)
             .ok_or(ProcessServerError::InvalidUnixStat(
-                "/proc/[PID]/status had an invalid syntax".to_string(),
-            ))?;
-        let uids = status.get("Uid").ok_or(ProcessServerError::InvalidUnixStat(
-            "Missing uid map in /proc/[PID]/status".to_string(),
-        ))?;
-        let (real, _effective_saved_fs) = uids.split_once(|x: char| x.is_whitespace()).ok_or(
-            ProcessServerError::InvalidUnixStat(
-                "Invalid uid mapping, not whitespace separated".to_string(),
-            ),
-        )?;
-        let owner_id = real.parse().map_err(|e| {
-            ProcessServerError::InvalidUnixStat(format!(
-                "Invalid real uid, could not be casted to u32 size {e}"
+                "Missing starttime field".to_string(),
+            ))?
+            .parse()
+            .map_err(|x| {
+                ProcessServerError::InvalidUnixStat(format!(
+                    "The starttime field is not u64 size: {}",
+                    x
+                ))
+            })?;
+        let start_time = start_time_millis(root.boot_time()?, start_ticks);
+        // fields[11] and fields[12] are utime and stime (fields 14 and 15) in clock
+        // ticks
+        let cpu_field = |idx: usize| -> ProcessServerResult<u64> {
+            fields.get(idx).and_then(|x| x.parse().ok()).ok_or(ProcessServerError::InvalidUnixStat(
+                format!("Invalid field {}", idx + 3),
             ))
-        })?;
-        let owner_name = users::get_user_by_uid(owner_id)
-            .ok_or(ProcessServerError::UserNotFound(owner_id))?
-            .name()
-            .to_owned();
+        };
+        let cpu_times = CpuTimes::new(cpu_field(11)?, cpu_field(12)?);
+
+        let credentials = Credentials::from_status(root, &status_content)?;
+        let capabilities = Capabilities::from_status(&status_content)?;
+        let security = Security {
+            lsm_label: read_lsm_label(root, pid),
+            ..Security::from_status(&status_content)
+        };
+        let owner_id = credentials.uids().real;
+        let owner_name = root.user_name(owner_id)?;
 
         let mut args = cmdline.split(' ').map(String::from).collect::<Vec<_>>();
         // remove the last element of the split (empty because they are lines)
         let _last = args.pop();
-        // remove the executable since it doesn't matter (it will always be there)
-        let _executable = args.remove(0);
+        // remove the executable since it doesn't matter (it is missing for zombies and
+        // kernel threads)
+        if !args.is_empty() {
+            let _executable = args.remove(0);
+        }
 
+        let (namespaces, nspid) = read_namespaces(root, pid)?;
         Ok(Self {
             pid,
             ppid,
+            start_time,
             name,
             owner_id,
             owner_name,
             exe,
+            exe_status,
             cwd,
             cmdline,
             args,
+            credentials,
+            capabilities,
+            security,
+            cpu_times,
+            cpu_usage: None,
+            memory: Memory::read(root, pid)?,
+            threads: read_threads_if_enumerated(root, pid),
+            fds: read_fds_if_enabled(root, pid),
+            sockets: None,
+            environ: read_environ_if_enabled(root, pid),
+            mappings: read_mappings_if_enabled(root, pid),
+            exe_sha256: read_exe_hash_if_enabled(root, pid),
+            cgroup: read_cgroup(root, pid),
+            namespaces,
+            nspid,
+            fingerprint: None,
         })
     }
 }
@@ -153,9 +180,19 @@ impl UnixProcess {
 #[derive(Default)]
 pub struct ManualProbe {
     pub(crate) cache: UnixProcessCache,
+    pub(crate) root:  ProcRoot,
+}
+
+impl ManualProbe {
+    pub fn new(root: ProcRoot) -> Self {
+        Self {
+            cache: Default::default(),
+            root,
+        }
+    }
 }
 
-impl_unix_probe!(ManualProbe, UnixProcess::from_manual);
+impl_unix_probe!(ManualProbe, UnixProcess::from_manual_at);
 
 
 #[cfg(test)]
@@ -172,4 +209,19 @@ mod tests {
         debug!("{:?}", process);
         command.kill().expect("works");
     }
+
+    #[test_log::test]
+    fn test_start_time_agree() {
+        use crate::StaticProcess;
+        let mut command = Command::new("sleep").arg("10").spawn().expect("works");
+        // let the child exec so all the probes read the same cmdline
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let manual = UnixProcess::from_manual(command.id()).expect("works");
+        let procfs = UnixProcess::from_procfs(command.id()).expect("works");
+        let psutil = UnixProcess::from_psutil(command.id()).expect("works");
+        command.kill().expect("works");
+        command.wait().expect("works");
+        assert_eq!(manual.identity(), procfs.identity());
+        assert_eq!(manual.identity(), psutil.identity());
+    }
 }
@@ -0,0 +1,206 @@
+use crate::{
+    process::{unix::ProcRoot, Pid},
+    ProcessServerError,
+    ProcessServerResult,
+};
+use std::{collections::HashMap, ffi::OsStr, os::unix::ffi::OsStrExt, path::PathBuf};
+
+const MAPS: &str = "maps";
+const DELETED_SUFFIX: &[u8] = b" (deleted)";
+const MEMFD_PREFIX: &[u8] = b"/memfd:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MappingKind {
+    File,
+    /// A `memfd_create` file, it has no path on any filesystem
+    Memfd,
+    /// Executable memory without a file, e.g. JIT code (or injected code)
+    Anonymous,
+}
+
+impl MappingKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MappingKind::File => "file",
+            MappingKind::Memfd => "memfd",
+            MappingKind::Anonymous => "anonymous",
+        }
+    }
+}
+
+/// A file mapped by a process, the regions of the same file are merged, see
+/// `/proc/[pid]/maps` in https://man7.org/linux/man-pages/man5/proc.5.html
+///
+/// Note: the anonymous mappings are only kept when executable, one per region,
+/// the kernel ones (`[heap]`, `[vdso]`, ...) are skipped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    pub kind:       MappingKind,
+    /// The file without the ` (deleted)` suffix, `/memfd:[name]` for a memfd
+    /// and `None` when anonymous
+    pub path:       Option<PathBuf>,
+    pub inode:      u64,
+    /// The file was removed (or replaced, e.g. by a package upgrade) since it
+    /// was mapped, the memfds are never deleted here though the kernel says so
+    pub deleted:    bool,
+    /// At least one region is executable
+    pub executable: bool,
+    /// The address of the first region
+    pub start:      u64,
+    /// The size of all the regions in bytes
+    pub size:       u64,
+}
+
+impl Mapping {
+    /// A shared library by its name, `lib.so` or `lib.so.[version]`
+    pub fn is_library(&self) -> bool {
+        let Some(name) = self.path.as_ref().and_then(|x| x.file_name()) else {
+            return false;
+        };
+        let name = name.as_bytes();
+        self.kind == MappingKind::File
+            && (name.ends_with(b".so") || name.windows(4).any(|x| x == b".so."))
+    }
+}
+
+/// A line of the maps, `start-end perms offset dev inode [path]`
+struct Region<'a> {
+    start:      u64,
+    end:        u64,
+    executable: bool,
+    inode:      u64,
+    path:       &'a [u8],
+}
+
+fn parse_region(line: &[u8]) -> Option<Region<'_>> {
+    let mut rest = line;
+    let mut fields = [&[][..]; 5];
+    for field in fields.iter_mut() {
+        rest = &rest[rest.iter().position(|x| *x != b' ')?..];
+        let end = rest.iter().position(|x| *x == b' ').unwrap_or(rest.len());
+        (*field, rest) = rest.split_at(end);
+    }
+    let text = |x: &[u8]| std::str::from_utf8(x).ok().map(String::from);
+    let range = text(fields[0])?;
+    let (start, end) = range.split_once('-')?;
+    // the path is after a padding of spaces, it may hold spaces itself
+    let path = &rest[rest.iter().position(|x| *x != b' ').unwrap_or(rest.len())..];
+    Some(Region {
+        start: u64::from_str_radix(start, 16).ok()?,
+        end: u64::from_str_radix(end, 16).ok()?,
+        executable: fields[1].get(2) == Some(&b'x'),
+        inode: text(fields[4])?.parse().ok()?,
+        path,
+    })
+}
+
+pub(crate) fn parse_maps(content: &[u8]) -> ProcessServerResult<Vec<Mapping>> {
+    let mut mappings: Vec<Mapping> = vec![];
+    let mut files = HashMap::<(&[u8], u64), usize>::new();
+    for line in content.split(|x| *x == b'\n').filter(|x| !x.is_empty()) {
+        let region = parse_region(line).ok_or_else(|| {
+            ProcessServerError::InvalidUnixStat(format!(
+                "Invalid mapping {}",
+                String::from_utf8_lossy(line)
+            ))
+        })?;
+        let size = region.end - region.start;
+        let kind = if region.path.starts_with(MEMFD_PREFIX) {
+            MappingKind::Memfd
+        } else if region.path.starts_with(b"/") {
+            MappingKind::File
+        } else if region.executable
+            && (region.path.is_empty() || region.path.starts_with(b"[anon:"))
+        {
+            MappingKind::Anonymous
+        } else {
+            continue;
+        };
+        if kind == MappingKind::Anonymous {
+            mappings.push(Mapping {
+                kind,
+                path: None,
+                inode: 0,
+                deleted: false,
+                executable: true,
+                start: region.start,
+                size,
+            });
+            continue;
+        }
+        if let Some(&idx) = files.get(&(region.path, region.inode)) {
+            let mapping = &mut mappings[idx];
+            mapping.executable |= region.executable;
+            mapping.size += size;
+            continue;
+        }
+        let (path, deleted) = match region.path.strip_suffix(DELETED_SUFFIX) {
+            Some(path) => (path, kind == MappingKind::File),
+            None => (region.path, false),
+        };
+        files.insert((region.path, region.inode), mappings.len());
+        mappings.push(Mapping {
+            kind,
+            path: Some(PathBuf::from(OsStr::from_bytes(path))),
+            inode: region.inode,
+            deleted,
+            executable: region.executable,
+            start: region.start,
+            size,
+        });
+    }
+    Ok(mappings)
+}
+
+/// Read the files mapped by a process, in address order
+///
+/// Note: reading the maps of a process needs the right to trace it
+pub(crate) fn read_mappings(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Vec<Mapping>> {
+    parse_maps(&std::fs::read(root.process_path(pid).join(MAPS))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_parse_maps() {
+        let content = concat!(
+            "55d0c0a00000-55d0c0a02000 r--p 00000000 fd:01 1234    /usr/bin/app\n",
+            "55d0c0a02000-55d0c0a05000 r-xp 00002000 fd:01 1234    /usr/bin/app\n",
+            "55d0c1b00000-55d0c1b21000 rw-p 00000000 00:00 0       [heap]\n",
+            "7f1a2b000000-7f1a2b001000 rwxp 00000000 00:00 0 \n",
+            "7f1a2c000000-7f1a2c100000 r-xp 00000000 fd:01 5678    ",
+            "/usr/lib/libssl.so.3 (deleted)\n",
+            "7f1a2d000000-7f1a2d001000 r-xs 00000000 00:01 42      /memfd:jit (deleted)\n",
+            "7ffc1e5f0000-7ffc1e5f2000 r-xp 00000000 00:00 0       [vdso]\n",
+        );
+        let mappings = parse_maps(content.as_bytes()).expect("works");
+        let kinds = mappings.iter().map(|x| x.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                MappingKind::File,
+                MappingKind::Anonymous,
+                MappingKind::File,
+                MappingKind::Memfd
+            ]
+        );
+        assert_eq!(mappings[0].size, 0x5000);
+        assert!(mappings[0].executable);
+        assert!(!mappings[0].is_library());
+        assert_eq!(
+            mappings[2].path,
+            Some(PathBuf::from("/usr/lib/libssl.so.3"))
+        );
+        assert!(mappings[2].deleted && mappings[2].is_library());
+        assert!(!mappings[3].deleted);
+    }
+
+    #[test_log::test]
+    fn test_read_mappings() {
+        let mappings = read_mappings(&ProcRoot::default(), std::process::id()).expect("works");
+        let exe = std::env::current_exe().expect("works");
+        assert!(mappings.iter().any(|x| x.path.as_ref() == Some(&exe)));
+    }
+}
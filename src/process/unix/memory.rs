@@ -0,0 +1,131 @@
+use crate::{
+    process::{unix::ProcRoot, Pid},
+    ProcessServerError,
+    ProcessServerResult,
+};
+use tracing::debug;
+
+const STATM: &str = "statm";
+const STATUS: &str = "status";
+const SMAPS_ROLLUP: &str = "smaps_rollup";
+
+/// The memory of a process in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Memory {
+    /// The virtual memory size
+    pub vsz:          u64,
+    /// The resident set size
+    pub rss:          u64,
+    /// The resident pages backed by a file (shared libraries, mapped files,
+    /// ...), they may be shared with other processes
+    pub shared:       u64,
+    pub swap:         u64,
+    /// Only read when asked, see [`ProcRoot::smaps_rollup`]
+    pub proportional: Option<ProportionalMemory>,
+}
+
+/// What a process really costs, the shared pages are split between the
+/// processes mapping them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProportionalMemory {
+    /// The proportional set size, the private pages plus a share of the shared
+    /// ones
+    pub pss: u64,
+    /// The unique set size, the memory freed if the process exited
+    pub uss: u64,
+}
+
+impl Memory {
+    /// Read `/proc/[pid]/statm` and the swap of `/proc/[pid]/status`, along
+    /// with `/proc/[pid]/smaps_rollup` if the root asks for it
+    pub(crate) fn read(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Self> {
+        let process_path = root.process_path(pid);
+        // See definition in https://man7.org/linux/man-pages/man5/proc.5.html at `/proc/[pid]/statm`
+        let statm = std::fs::read_to_string(process_path.join(STATM))?;
+        let pages = statm
+            .split_whitespace()
+            .map(|x| x.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ProcessServerError::InvalidUnixStat(format!("Invalid statm {e}")))?;
+        let [size, resident, shared, ..] = pages[..] else {
+            return Err(ProcessServerError::InvalidUnixStat(format!(
+                "Expected 7 statm fields, got {}",
+                pages.len()
+            )));
+        };
+        // Note: the kernel threads have no memory lines at all
+        let status = std::fs::read_to_string(process_path.join(STATUS))?;
+        let swap = kilobytes(&status, "VmSwap").unwrap_or(0);
+        let proportional = if root.smaps_rollup() {
+            read_smaps_rollup(root, pid)
+        } else {
+            None
+        };
+        let page_size = page_size();
+        Ok(Self {
+            vsz: size * page_size,
+            rss: resident * page_size,
+            shared: shared * page_size,
+            swap,
+            proportional,
+        })
+    }
+}
+
+/// Note: `smaps_rollup` needs Linux 4.14 and the right to trace the process,
+/// the proportional memory is unknown otherwise
+fn read_smaps_rollup(root: &ProcRoot, pid: Pid) -> Option<ProportionalMemory> {
+    // See definition in https://man7.org/linux/man-pages/man5/proc.5.html at `/proc/[pid]/smaps_rollup`
+    let content = match std::fs::read_to_string(root.process_path(pid).join(SMAPS_ROLLUP)) {
+        Ok(content) => content,
+        Err(e) => {
+            debug!("Could not read smaps_rollup of {pid} {e}");
+            return None;
+        },
+    };
+    Some(ProportionalMemory {
+        pss: kilobytes(&content, "Pss")?,
+        uss: kilobytes(&content, "Private_Clean")? + kilobytes(&content, "Private_Dirty")?,
+    })
+}
+
+/// The value in bytes of a `Key:   123 kB` line
+fn kilobytes(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name != key {
+            return None;
+        }
+        let value = value.trim().strip_suffix("kB").unwrap_or(value).trim();
+        value.parse::<u64>().ok().map(|x| x * 1024)
+    })
+}
+
+fn page_size() -> u64 {
+    // SAFETY: sysconf has no precondition, it only reads a system constant
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size <= 0 {
+        4096
+    } else {
+        size as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_kilobytes() {
+        let content = concat!(
+            "Rss:                3872 kB\n",
+            "Pss:                1157 kB\n",
+            "Pss_Anon:            300 kB\n",
+            "Private_Clean:       100 kB\n",
+            "Private_Dirty:       412 kB\n",
+        );
+        assert_eq!(kilobytes(content, "Pss"), Some(1157 * 1024));
+        assert_eq!(kilobytes(content, "Private_Dirty"), Some(412 * 1024));
+        assert_eq!(kilobytes(content, "VmSwap"), None);
+    }
+}
@@ -1,19 +1,146 @@
-use crate::process::{Pid, ProcessProbe};
-use std::{ffi::OsString, path::PathBuf};
+use crate::{
+    process::{Collection, CollectionTiming, Pid, ProcessProbe},
+    ProcessServerResult,
+};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
 
-pub(crate) const PROC_PATH: &str = "/proc";
+const STATUS: &str = "status";
 
 #[derive(Debug, Clone)]
 pub struct UnixProcess {
-    pid:        Pid,
-    ppid:       Pid,
-    name:       String,
-    owner_id:   u32,
-    owner_name: OsString,
-    exe:        PathBuf,
-    cwd:        PathBuf,
-    cmdline:    String,
-    args:       Vec<String>,
+    pid:          Pid,
+    ppid:         Pid,
+    start_time:   u64,
+    name:         String,
+    owner_id:     u32,
+    owner_name:   OsString,
+    // the link as read, with the ` (deleted)` suffix of the removed files
+    exe:          PathBuf,
+    exe_status:   ExeStatus,
+    cwd:          PathBuf,
+    cmdline:      String,
+    args:         Vec<String>,
+    credentials:  Credentials,
+    capabilities: Capabilities,
+    security:     Security,
+    cpu_times:    CpuTimes,
+    // computed against the previous acquisition of the same process
+    cpu_usage:    Option<CpuUsage>,
+    memory:       Memory,
+    // `None` unless the threads are enumerated, see `ProcRoot::enumerate_threads`
+    threads:      Option<Vec<Thread>>,
+    // `None` unless the fds are read and readable, see `ProcRoot::fds`
+    fds:          Option<Vec<Fd>>,
+    // the sockets of the fds, `None` when the fds are not read
+    sockets:      Option<Vec<Socket>>,
+    // `None` unless the environment is read, see `ProcRoot::environ`
+    environ:      Option<Environ>,
+    // `None` unless the maps are read and readable, see `ProcRoot::maps`
+    mappings:     Option<Vec<Mapping>>,
+    // `None` unless the executables are hashed, see `ProcRoot::exe_hash`
+    exe_sha256:   Option<[u8; 32]>,
+    // `None` when there is no cgroup (or it can't be read)
+    cgroup:       Option<Cgroup>,
+    namespaces:   Namespaces,
+    // from the outermost pid namespace, the last one is the pid in the container
+    nspid:        Vec<Pid>,
+    // set when read by an incremental collection, see `ProcRoot::incremental`
+    fingerprint:  Option<Fingerprint>,
+}
+
+impl UnixProcess {
+    /// All the user and group ids, [`StaticProcess::owner_id`] is only the real
+    /// uid
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// A process of a non root user holding dangerous effective capabilities
+    pub fn has_dangerous_capabilities(&self) -> bool {
+        self.capabilities.is_dangerous(self.credentials.uids().effective)
+    }
+
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+
+    pub fn cpu_times(&self) -> CpuTimes {
+        self.cpu_times
+    }
+
+    /// The CPU used since the previous acquisition, `None` for the first
+    /// acquisition of a process
+    pub fn cpu_usage(&self) -> Option<CpuUsage> {
+        self.cpu_usage
+    }
+
+    pub fn memory(&self) -> Memory {
+        self.memory
+    }
+
+    /// The threads, `None` unless they are enumerated
+    pub fn threads(&self) -> Option<&[Thread]> {
+        self.threads.as_deref()
+    }
+
+    /// The open file descriptors, `None` unless they are read
+    pub fn fds(&self) -> Option<&[Fd]> {
+        self.fds.as_deref()
+    }
+
+    /// The sockets the process has open, `None` unless the fds are read
+    pub fn sockets(&self) -> Option<&[Socket]> {
+        self.sockets.as_deref()
+    }
+
+    /// The environment, `None` unless it is read
+    pub fn environ(&self) -> Option<&Environ> {
+        self.environ.as_ref()
+    }
+
+    pub fn exe_status(&self) -> ExeStatus {
+        self.exe_status
+    }
+
+    /// The SHA-256 of the executable that is running, `None` unless hashed
+    pub fn exe_sha256(&self) -> Option<[u8; 32]> {
+        self.exe_sha256
+    }
+
+    /// The mapped files, `None` unless the maps are read
+    pub fn mappings(&self) -> Option<&[Mapping]> {
+        self.mappings.as_deref()
+    }
+
+    /// The cgroups along the container and the systemd unit of the process
+    pub fn cgroup(&self) -> Option<&Cgroup> {
+        self.cgroup.as_ref()
+    }
+
+    pub fn namespaces(&self) -> Namespaces {
+        self.namespaces
+    }
+
+    /// The pid of the process in each of its nested pid namespaces, the first
+    /// one is the pid in the namespace of the root
+    pub fn nspid(&self) -> &[Pid] {
+        &self.nspid
+    }
+
+    /// The pid as seen inside the container (or the innermost pid namespace)
+    pub fn namespaced_pid(&self) -> Pid {
+        self.nspid.last().copied().unwrap_or(self.pid)
+    }
 }
 
 impl StaticProcess for UnixProcess {
@@ -25,6 +152,10 @@ impl StaticProcess for UnixProcess {
         self.ppid
     }
 
+    fn start_time(&self) -> u64 {
+        self.start_time
+    }
+
     fn name(&self) -> String {
         self.name.clone()
     }
@@ -55,52 +186,326 @@ impl StaticProcess for UnixProcess {
 }
 
 mod cache;
+mod capabilities;
+mod cgroup;
+mod cpu;
+mod credentials;
+mod environ;
+mod exe;
+mod exe_hash;
+mod fds;
+mod fingerprint;
 mod manual_probe;
+mod maps;
+mod memory;
+mod namespaces;
+mod netlink_probe;
 mod procfs_probe;
 mod psutil_probe;
+mod root;
+mod security;
+mod sockets;
+mod threads;
 
 use crate::process::traits::StaticProcess;
 pub use cache::UnixProcessCache;
+pub use capabilities::{Capabilities, CapabilitySet};
+pub use cgroup::{Cgroup, CgroupEntry, Container, Runtime};
+pub(crate) use cpu::online_cores;
+pub use cpu::{CpuTimes, CpuUsage};
+pub use credentials::{Credentials, IdSet};
+pub use environ::Environ;
+pub(crate) use exe::read_exe;
+pub use exe::ExeStatus;
+pub use fds::{Fd, FdKind};
+use fingerprint::Fingerprint;
 pub use manual_probe::ManualProbe as UnixManualProbe;
+pub use maps::{Mapping, MappingKind};
+pub use memory::{Memory, ProportionalMemory};
+pub use namespaces::Namespaces;
+pub use netlink_probe::NetlinkProbe as UnixNetlinkProbe;
 pub use procfs_probe::ProcfsProbe as UnixProcfsProbe;
 pub use psutil_probe::PsutilProbe as UnixPsutilProbe;
+pub use root::{ProcRoot, UnixProbeConfig};
+pub(crate) use security::read_lsm_label;
+pub use security::{SeccompMode, Security};
+pub use sockets::{Protocol, Socket};
+pub use threads::Thread;
 
 pub type UnixProcessProbe = Box<dyn ProcessProbe<UnixProcess>>;
 
+/// The number of clock ticks per second (`sysconf(_SC_CLK_TCK)`), times in
+/// `/proc/[pid]/stat` are expressed in this unit
+pub(crate) fn ticks_per_second() -> u64 {
+    // SAFETY: sysconf has no precondition, it only reads a system constant
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks <= 0 {
+        // this is the value on virtually all linux systems
+        100
+    } else {
+        ticks as u64
+    }
+}
+
+/// Convert a start time expressed in clock ticks since boot (field 22 of
+/// `/proc/[pid]/stat`) to milliseconds since the Unix epoch
+///
+/// Note: all the unix probes use this conversion so they agree on the identity
+/// of a process
+pub(crate) fn start_time_millis(boot_time_secs: u64, start_ticks: u64) -> u64 {
+    boot_time_secs * 1000 + start_ticks * 1000 / ticks_per_second()
+}
+
+/// Read an optional detail of a process when enabled
+///
+/// Note: the details are not worth failing the whole process, e.g. the fds of
+/// the processes of other users can't be read
+fn read_enabled<T>(
+    enabled: bool,
+    pid: Pid,
+    what: &str,
+    read: impl FnOnce() -> ProcessServerResult<T>,
+) -> Option<T> {
+    if !enabled {
+        return None;
+    }
+    match read() {
+        Ok(value) => Some(value),
+        Err(e) => {
+            tracing::debug!("Could not read the {what} of {pid} {e}");
+            None
+        },
+    }
+}
+
+pub(crate) fn read_threads_if_enumerated(root: &ProcRoot, pid: Pid) -> Option<Vec<Thread>> {
+    read_enabled(root.enumerate_threads(), pid, "threads", || {
+        threads::read_threads(root, pid)
+    })
+}
+
+pub(crate) fn read_status(root: &ProcRoot, pid: Pid) -> ProcessServerResult<String> {
+    Ok(std::fs::read_to_string(root.process_path(pid).join(STATUS))?)
+}
+
+pub(crate) fn read_namespaces(
+    root: &ProcRoot,
+    pid: Pid,
+) -> ProcessServerResult<(Namespaces, Vec<Pid>)> {
+    Ok((
+        Namespaces::read(root, pid),
+        namespaces::read_nspid(root, pid)?,
+    ))
+}
+
+pub(crate) fn read_cgroup(root: &ProcRoot, pid: Pid) -> Option<Cgroup> {
+    read_enabled(true, pid, "cgroup", || Cgroup::read(root, pid))
+}
+
+pub(crate) fn read_environ_if_enabled(root: &ProcRoot, pid: Pid) -> Option<Environ> {
+    read_enabled(root.environ(), pid, "environment", || {
+        Environ::read(root, pid)
+    })
+}
+
+pub(crate) fn read_exe_hash_if_enabled(root: &ProcRoot, pid: Pid) -> Option<[u8; 32]> {
+    root.exe_hashes()?.hash(root, pid, root.exe_hash_deadline())
+}
+
+pub(crate) fn read_mappings_if_enabled(root: &ProcRoot, pid: Pid) -> Option<Vec<Mapping>> {
+    read_enabled(root.maps(), pid, "maps", || maps::read_mappings(root, pid))
+}
+
+pub(crate) fn read_fds_if_enabled(root: &ProcRoot, pid: Pid) -> Option<Vec<Fd>> {
+    read_enabled(root.fds(), pid, "fds", || fds::read_fds(root, pid))
+}
+
+/// Read all the processes of a root, on `root.threads()` threads
+///
+/// When the collection is incremental, the `previous` processes (by pid) whose
+/// fingerprint did not change are reused instead of being read again
+///
+/// Note: the processes are ordered by pid whatever the number of threads, so
+/// the snapshots and the events do not depend on it
+pub(crate) fn collect_root(
+    root: &ProcRoot,
+    read: fn(&ProcRoot, Pid) -> ProcessServerResult<UnixProcess>,
+    previous: HashMap<Pid, UnixProcess>,
+) -> std::io::Result<Collection<UnixProcess>> {
+    let started = Instant::now();
+    let mut pids = vec![];
+    for entry in std::fs::read_dir(root.path())? {
+        match entry {
+            // Note we don't check if it is a directory as this can also fail due to metadata
+            // permissions, we assume the layout of `/proc`
+            Ok(entry) => match entry.file_name().to_string_lossy().parse::<Pid>() {
+                Ok(pid) => pids.push(pid),
+                Err(_) => tracing::trace!("This entry is not a pid {:?}", entry.file_name()),
+            },
+            // Note if we can not read it, it surely is not an error, this can be a lack of
+            // priviledge, a directory being removed while iterating and much more, on Unix
+            // there is no way to freeze (lock) correctly a filesystem while operating on it
+            Err(err) => tracing::debug!("Could not read this entry with error {}", err),
+        }
+    }
+    pids.sort_unstable();
+    // the time budget of the exe hashes is for this acquisition only
+    let root = &root.start_acquisition();
+
+    let reused = AtomicUsize::new(0);
+    let read_pid = |pid: Pid| {
+        let result = if root.incremental() {
+            Fingerprint::read(root, pid).and_then(|(fingerprint, cpu_times)| {
+                match previous.get(&pid) {
+                    Some(process) if process.fingerprint.as_ref() == Some(&fingerprint) => {
+                        reused.fetch_add(1, Ordering::Relaxed);
+                        let mut process = process.clone();
+                        // what a process changes itself (ids, capabilities, confinement,
+                        // threads, fds, libraries, environment, namespaces...) can only
+                        // change if it ran, see `ProcRoot::incremental` for what is missed
+                        let ran = process.cpu_times.total_ticks() != cpu_times.total_ticks();
+                        process.cpu_times = cpu_times;
+                        if ran {
+                            process.memory = Memory::read(root, pid)?;
+                            let status = read_status(root, pid)?;
+                            process.credentials = Credentials::from_status(root, &status)?;
+                            process.owner_id = process.credentials.uids().real;
+                            process.owner_name = root.user_name(process.owner_id)?;
+                            process.capabilities = Capabilities::from_status(&status)?;
+                            process.security = Security {
+                                lsm_label: read_lsm_label(root, pid),
+                                ..Security::from_status(&status)
+                            };
+                            process.threads = read_threads_if_enumerated(root, pid);
+                            process.fds = read_fds_if_enabled(root, pid);
+                            process.environ = read_environ_if_enabled(root, pid);
+                            process.mappings = read_mappings_if_enabled(root, pid);
+                            (process.exe, process.exe_status) = read_exe(root, pid);
+                            process.cgroup = read_cgroup(root, pid);
+                            process.namespaces = Namespaces::read(root, pid);
+                        }
+                        // the exe does not change without an exec, unless it was not hashed
+                        if process.exe_sha256.is_none() {
+                            process.exe_sha256 = read_exe_hash_if_enabled(root, pid);
+                        }
+                        return Ok(process);
+                    },
+                    _ => {},
+                }
+                let mut process = read(root, pid)?;
+                process.fingerprint = Some(fingerprint);
+                Ok(process)
+            })
+        } else {
+            read(root, pid)
+        };
+        match result {
+            Ok(process) => Some(process),
+            Err(err) => {
+                tracing::debug!("Could not read process for pid {} with error {}", pid, err);
+                None
+            },
+        }
+    };
+    let threads = root.threads().clamp(1, pids.len().max(1));
+    let processes = if threads == 1 {
+        pids.iter().map(|&pid| read_pid(pid)).collect::<Vec<_>>()
+    } else {
+        // the pids are handed out one by one so a slow process does not delay a
+        // whole chunk
+        let next = AtomicUsize::new(0);
+        let mut read = std::thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut read = vec![];
+                        while let Some(&pid) = pids.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            read.push((pid, read_pid(pid)));
+                        }
+                        read
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect::<Vec<_>>()
+        });
+        read.sort_unstable_by_key(|(pid, _)| *pid);
+        read.into_iter().map(|(_, process)| process).collect()
+    };
+
+    let mut processes = processes.into_iter().flatten().collect::<Vec<_>>();
+    if root.fds() {
+        sockets::attach_sockets(root, &mut processes);
+    }
+    let timing = CollectionTiming {
+        threads,
+        pids: pids.len(),
+        failed: pids.len() - processes.len(),
+        reused: reused.into_inner(),
+        duration_us: started.elapsed().as_micros() as u64,
+    };
+    tracing::debug!("Collected {:?}", timing);
+    Ok(Collection { processes, timing })
+}
+
 macro_rules! impl_unix_probe {
     ($probe:ty, $method:path) => {
+        $crate::impl_unix_probe!($probe, $method, {});
+    };
+    // an event driven probe implements `drain_kernel_events` and `kernel_events` itself, as
+    // well as `scan_started` and `replay_kernel_events` so the events applied during a scan
+    // are not lost when its processes are published
+    ($probe:ty, $method:path, event_driven) => {
+        $crate::impl_unix_probe!($probe, $method, {
+            fn apply_pending_events(&mut self) -> usize {
+                <$probe>::drain_kernel_events(self)
+            }
+
+            fn pending_events(&self) -> Option<std::sync::Arc<tokio::sync::Notify>> {
+                <$probe>::kernel_events(self)
+            }
+        }, <$probe>::scan_started, <$probe>::replay_kernel_events);
+    };
+    ($probe:ty, $method:path, { $($extra:tt)* } $(, $started:expr, $replay:expr)?) => {
         impl $crate::process::ProcessProbe<$crate::process::UnixProcess> for $probe {
-            fn collect_processes(&mut self) -> std::io::Result<Vec<&$crate::process::UnixProcess>> {
+            fn collector(&self, options: $crate::process::AcquisitionOptions) -> $crate::process::ProcessCollector<$crate::process::UnixProcess> {
+                use $crate::cache::Cache;
+                let root = self.root.clone().with_options(options);
+                // only the processes read by an incremental collection can be reused
+                let previous = self.cache.get()
+                    .into_iter()
+                    .filter(|process| process.fingerprint.is_some())
+                    .map(|process| (process.pid, process.clone()))
+                    .collect();
+                $( ($started)(self); )?
+                Box::new(move || {
+                    tracing::debug!("Called collect processes on unix probe");
+                    $crate::process::unix::collect_root(&root, $method, previous)
+                })
+            }
+
+            fn publish_processes(&mut self, collection: $crate::process::Collection<$crate::process::UnixProcess>) -> Vec<&$crate::process::UnixProcess> {
                 use $crate::cache::Cache;
-                tracing::debug!("Called collect processes on unix probe");
-                let entries = std::fs::read_dir($crate::process::PROC_PATH)?;
-                // we can clear the cache here as we can ensure no early return will happen after
+                use $crate::process::StaticProcess;
+                let previous = self.cache.get()
+                    .into_iter()
+                    .map(|process| (process.identity(), process.cpu_times))
+                    .collect::<std::collections::HashMap<_, _>>();
+                let cores = $crate::process::unix::online_cores();
                 self.cache.clear();
-                for entry in entries {
-                    match entry {
-                        Ok(entry) => {
-                            // Note we don't check if it is a directory as this can also fail due to metadata permissions,
-                            // we assume the layout of `/proc`
-                            let pid = match entry.file_name().to_string_lossy().parse::<Pid>() {
-                                Ok(pid) => pid,
-                                Err(_) => {
-                                    tracing::trace!("This entry is not a pid {:?}",entry.file_name());
-                                    continue;
-                                }
-                            };
-                            match $method(pid) {
-                                Ok(process) => { self.cache.add(process); }
-                                Err(err) => { tracing::debug!("Could not read process for pid {} with error {}",pid,err) }
-                            }
-                        }
-                        Err(err) => {
-                            // Note if we can not read it, it surely is not an error, this can be a lack of priviledge, a directory being removed
-                            // while iterating and much more, on Unix there is no way to freeze (lock) correctly a filesystem while operating on it
-                            tracing::debug!("Could not read this entry with error {}",err);
-                        }
-                    }
+                let processes = collection.processes;
+                $( let processes = ($replay)(self, processes); )?
+                for mut process in processes {
+                    process.cpu_usage = previous
+                        .get(&process.identity())
+                        .and_then(|before| process.cpu_times.usage_since(before, cores));
+                    self.cache.add(process);
                 }
-                Ok(self.get_cached_processes())
+                self.cache.inner_mut().set_timing(collection.timing);
+                self.cache.finish_acquisition();
+                self.get_cached_processes()
             }
 
             fn get_cached_processes(&self) -> Vec<&$crate::process::UnixProcess> {
@@ -108,16 +513,30 @@ macro_rules! impl_unix_probe {
                 self.cache.get()
             }
 
-             fn obtain_channel(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<$crate::process::UnixProcess>{
+             fn obtain_channel(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<$crate::process::ProcessEvent<$crate::process::UnixProcess>>{
                 use $crate::cache::ChannelCache;
                 self.cache.subscribe()
              }
+
+            fn process_cache(&self) -> &$crate::process::ChannelProcessCache<$crate::process::UnixProcess> {
+                &self.cache
+            }
+
+            fn process_cache_mut(&mut self) -> &mut $crate::process::ChannelProcessCache<$crate::process::UnixProcess> {
+                &mut self.cache
+            }
+
+            $($extra)*
         }
 
         impl $probe {
             pub fn boxed(self) -> $crate::process::UnixProcessProbe {
                 Box::new(self)
             }
+
+            pub fn root(&self) -> &$crate::process::ProcRoot {
+                &self.root
+            }
         }
     };
 }
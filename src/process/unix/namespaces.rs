@@ -0,0 +1,97 @@
+use crate::{
+    process::{unix::ProcRoot, Pid},
+    ProcessServerError,
+    ProcessServerResult,
+};
+
+const NS: &str = "ns";
+const STATUS: &str = "status";
+
+/// The namespaces of a process by the inode of `/proc/[pid]/ns/[type]`, two
+/// processes with the same inode share the namespace, see
+/// https://man7.org/linux/man-pages/man7/namespaces.7.html
+///
+/// Note: the links can only be read with the right to trace the process, the
+/// namespaces are `None` otherwise (or when the kernel lacks them)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Namespaces {
+    pub pid:    Option<u64>,
+    pub mnt:    Option<u64>,
+    pub net:    Option<u64>,
+    pub user:   Option<u64>,
+    pub uts:    Option<u64>,
+    pub ipc:    Option<u64>,
+    pub cgroup: Option<u64>,
+}
+
+impl Namespaces {
+    pub(crate) fn read(root: &ProcRoot, pid: Pid) -> Self {
+        let ns_path = root.process_path(pid).join(NS);
+        let inode = |name: &str| {
+            let link = ns_path.join(name).read_link().ok()?;
+            // `type:[inode]`
+            let link = link.to_str()?.strip_prefix(name)?;
+            link.strip_prefix(":[")?.strip_suffix(']')?.parse().ok()
+        };
+        Self {
+            pid:    inode("pid"),
+            mnt:    inode("mnt"),
+            net:    inode("net"),
+            user:   inode("user"),
+            uts:    inode("uts"),
+            ipc:    inode("ipc"),
+            cgroup: inode("cgroup"),
+        }
+    }
+
+    /// None of the namespaces could be read
+    pub fn is_unknown(&self) -> bool {
+        self.iter().all(|(_, x)| x.is_none())
+    }
+
+    /// The namespaces by name, in the order of the fields
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, Option<u64>)> {
+        [
+            ("pid", self.pid),
+            ("mnt", self.mnt),
+            ("net", self.net),
+            ("user", self.user),
+            ("uts", self.uts),
+            ("ipc", self.ipc),
+            ("cgroup", self.cgroup),
+        ]
+        .into_iter()
+    }
+}
+
+/// Read the `NSpid:` line of `/proc/[pid]/status`, the pid of the process in
+/// each of its nested pid namespaces from the outermost one
+///
+/// Note: the line exists since Linux 4.1, the pid is alone before
+pub(crate) fn read_nspid(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Vec<Pid>> {
+    let status = std::fs::read_to_string(root.process_path(pid).join(STATUS))?;
+    match status.lines().find_map(|x| x.strip_prefix("NSpid:")) {
+        Some(line) => line
+            .split_whitespace()
+            .map(|x| x.parse::<Pid>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ProcessServerError::InvalidUnixStat(format!("Invalid NSpid {e}"))),
+        None => Ok(vec![pid]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_read() {
+        let root = ProcRoot::default();
+        let pid = std::process::id();
+        let namespaces = Namespaces::read(&root, pid);
+        assert!(namespaces.pid.is_some());
+        assert!(!namespaces.is_unknown() && Namespaces::default().is_unknown());
+        assert_eq!(namespaces, Namespaces::read(&root, pid));
+        assert_eq!(read_nspid(&root, pid).expect("works")[0], pid);
+    }
+}
@@ -0,0 +1,672 @@
+use crate::{
+    impl_unix_probe,
+    process::{
+        unix::{start_time_millis, CpuTimes, ExeStatus, ProcRoot, UnixProcess, UnixProcessCache},
+        Pid,
+    },
+    ProcessIdentity,
+    ProcessServerError,
+    ProcessServerResult,
+    StaticProcess,
+};
+use byteorder::{ByteOrder, NativeEndian};
+use parking_lot::{Condvar, Mutex};
+use std::{
+    collections::{BTreeMap, HashSet},
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+use tokio::sync::Notify;
+use tracing::{debug, warn};
+
+// See https://github.com/torvalds/linux/blob/master/include/uapi/linux/connector.h
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+// See https://github.com/torvalds/linux/blob/master/include/uapi/linux/cn_proc.h
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_FORK: u32 = 0x0000_0001;
+const PROC_EVENT_EXEC: u32 = 0x0000_0002;
+const PROC_EVENT_UID: u32 = 0x0000_0004;
+const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+
+// struct nlmsghdr
+const NLMSG_HEADER_LEN: usize = 16;
+// struct cn_msg without its data
+const CN_MSG_HEADER_LEN: usize = 20;
+// struct proc_event up to the event_data union (what, cpu, timestamp_ns)
+const PROC_EVENT_HEADER_LEN: usize = 16;
+const RECV_BUFFER_LEN: usize = 8192;
+
+/// The events we care about from the proc connector
+///
+/// Note: the kernel sends events for threads as well, we only keep the ones of
+/// thread group leaders (the processes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KernelEvent {
+    Fork { parent: Pid, child: Pid },
+    Exec(Pid),
+    Uid(Pid),
+    Exit(Pid),
+}
+
+/// A kernel event with its process read, `/proc` may not have it anymore once
+/// the event is applied
+#[derive(Debug, Clone)]
+enum Update {
+    Read(UnixProcess),
+    /// A minimal record from `/proc/[pid]/stat`, the process could not be
+    /// fully read (permissions, it is exiting)
+    Unreadable(UnixProcess),
+    Exit(Pid),
+}
+
+impl Update {
+    /// Read the processes of the events received together, with the time budget
+    /// of an acquisition
+    ///
+    /// Note: a process is read once even if it has several events (e.g. a fork
+    /// followed by an exec), unless it exited in between
+    fn read_all(root: &ProcRoot, events: Vec<KernelEvent>) -> Vec<Self> {
+        let root = root.start_acquisition();
+        let mut pending = HashSet::new();
+        let mut updates = vec![];
+        for event in events {
+            let pid = match event {
+                KernelEvent::Fork { child, .. } => child,
+                KernelEvent::Exec(pid) | KernelEvent::Uid(pid) => pid,
+                KernelEvent::Exit(pid) => {
+                    pending.remove(&pid);
+                    updates.push(Update::Exit(pid));
+                    continue;
+                },
+            };
+            if pending.insert(pid) {
+                updates.extend(Self::read(&root, pid));
+            }
+        }
+        updates
+    }
+
+    /// `None` when the process is gone, it would not be told apart from the
+    /// next process holding its pid
+    fn read(root: &ProcRoot, pid: Pid) -> Option<Self> {
+        let err = match UnixProcess::from_manual_at(root, pid) {
+            Ok(process) => return Some(Update::Read(process)),
+            Err(err) => err,
+        };
+        debug!("Could not read process for pid {} with error {}", pid, err);
+        match UnixProcess::from_stat(root, pid) {
+            Ok(process) => Some(Update::Unreadable(process)),
+            Err(err) => {
+                debug!("Could not read the stat of pid {} with error {}", pid, err);
+                None
+            },
+        }
+    }
+}
+
+impl UnixProcess {
+    /// What `/proc/[pid]/stat` tells of a process that can not be fully read,
+    /// this is enough for its identity
+    fn from_stat(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Self> {
+        let stat = std::fs::read_to_string(root.process_path(pid).join("stat"))?;
+        let (start, end) = stat.find('(').zip(stat.rfind(')')).ok_or(
+            ProcessServerError::InvalidUnixStat("Could not find comm".to_string()),
+        )?;
+        // fields[0] is state (field 3)
+        let fields = stat[end + 1..].split_whitespace().collect::<Vec<_>>();
+        let field = |idx: usize| -> ProcessServerResult<u64> {
+            fields.get(idx).and_then(|x| x.parse().ok()).ok_or(ProcessServerError::InvalidUnixStat(
+                format!("Invalid field {}", idx + 3),
+            ))
+        };
+        let start_time = start_time_millis(root.boot_time()?, field(19)?);
+        Ok(Self {
+            name: stat[start + 1..end].to_string(),
+            ..Self::placeholder(pid, field(1)? as Pid, start_time)
+        })
+    }
+
+    fn placeholder(pid: Pid, ppid: Pid, start_time: u64) -> Self {
+        Self {
+            pid,
+            ppid,
+            start_time,
+            name: String::new(),
+            // unknown, as the `-1` of `chown`
+            owner_id: u32::MAX,
+            owner_name: Default::default(),
+            exe: Default::default(),
+            exe_status: ExeStatus::Unreadable,
+            cwd: Default::default(),
+            cmdline: String::new(),
+            args: vec![],
+            credentials: Default::default(),
+            capabilities: Default::default(),
+            security: Default::default(),
+            cpu_times: CpuTimes::new(0, 0),
+            cpu_usage: None,
+            memory: Default::default(),
+            threads: None,
+            fds: None,
+            sockets: None,
+            environ: None,
+            mappings: None,
+            exe_sha256: None,
+            cgroup: None,
+            namespaces: Default::default(),
+            nspid: vec![pid],
+            fingerprint: None,
+        }
+    }
+}
+
+/// Parse a single `struct proc_event`
+fn parse_proc_event(data: &[u8]) -> Option<KernelEvent> {
+    if data.len() < PROC_EVENT_HEADER_LEN + 16 {
+        return None;
+    }
+    let what = NativeEndian::read_u32(&data[0..4]);
+    let event = &data[PROC_EVENT_HEADER_LEN..];
+    // every event we care about starts with (pid, tgid)
+    let pid = NativeEndian::read_u32(&event[0..4]);
+    let tgid = NativeEndian::read_u32(&event[4..8]);
+    match what {
+        PROC_EVENT_FORK => {
+            let child_pid = NativeEndian::read_u32(&event[8..12]);
+            let child_tgid = NativeEndian::read_u32(&event[12..16]);
+            (child_pid == child_tgid).then_some(KernelEvent::Fork {
+                parent: tgid,
+                child:  child_tgid,
+            })
+        },
+        PROC_EVENT_EXEC => (pid == tgid).then_some(KernelEvent::Exec(tgid)),
+        PROC_EVENT_UID => (pid == tgid).then_some(KernelEvent::Uid(tgid)),
+        PROC_EVENT_EXIT => (pid == tgid).then_some(KernelEvent::Exit(tgid)),
+        _ => None,
+    }
+}
+
+/// Parse all the netlink messages of a datagram
+fn parse_events(buffer: &[u8]) -> Vec<KernelEvent> {
+    let mut events = vec![];
+    let mut offset = 0;
+    while offset + NLMSG_HEADER_LEN <= buffer.len() {
+        let len = NativeEndian::read_u32(&buffer[offset..offset + 4]) as usize;
+        let typ = NativeEndian::read_u16(&buffer[offset + 4..offset + 6]);
+        if len < NLMSG_HEADER_LEN || offset + len > buffer.len() {
+            debug!("Truncated netlink message of {} bytes", len);
+            break;
+        }
+        if typ == libc::NLMSG_DONE as u16 {
+            let message = &buffer[offset + NLMSG_HEADER_LEN..offset + len];
+            if message.len() >= CN_MSG_HEADER_LEN
+                && NativeEndian::read_u32(&message[0..4]) == CN_IDX_PROC
+                && NativeEndian::read_u32(&message[4..8]) == CN_VAL_PROC
+            {
+                let data_len = NativeEndian::read_u16(&message[16..18]) as usize;
+                let data = &message[CN_MSG_HEADER_LEN..];
+                if let Some(event) = parse_proc_event(&data[..data_len.min(data.len())]) {
+                    events.push(event);
+                }
+            }
+        }
+        // messages are aligned on 4 bytes
+        offset += (len + 3) & !3;
+    }
+    events
+}
+
+/// The subscription message to the proc connector
+fn listen_message() -> [u8; NLMSG_HEADER_LEN + CN_MSG_HEADER_LEN + 4] {
+    let mut message = [0u8; NLMSG_HEADER_LEN + CN_MSG_HEADER_LEN + 4];
+    let len = message.len();
+    // struct nlmsghdr, flags, seq and pid are left to 0
+    NativeEndian::write_u32(&mut message[0..4], len as u32);
+    NativeEndian::write_u16(&mut message[4..6], libc::NLMSG_DONE as u16);
+    // struct cn_msg, seq, ack and flags are left to 0
+    let cn_msg = &mut message[NLMSG_HEADER_LEN..];
+    NativeEndian::write_u32(&mut cn_msg[0..4], CN_IDX_PROC);
+    NativeEndian::write_u32(&mut cn_msg[4..8], CN_VAL_PROC);
+    NativeEndian::write_u16(&mut cn_msg[16..18], 4);
+    // enum proc_cn_mcast_op
+    NativeEndian::write_u32(&mut cn_msg[CN_MSG_HEADER_LEN..], PROC_CN_MCAST_LISTEN);
+    message
+}
+
+/// Open a netlink socket subscribed to the proc connector
+///
+/// Note: this requires CAP_NET_ADMIN
+fn open_socket() -> io::Result<libc::c_int> {
+    // SAFETY: these are plain syscalls on a file descriptor we own, it is closed on
+    // every error path
+    unsafe {
+        let fd = libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_CONNECTOR,
+        );
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let close_on_error = |fd| {
+            let err = io::Error::last_os_error();
+            libc::close(fd);
+            Err(err)
+        };
+        let mut addr: libc::sockaddr_nl = std::mem::zeroed();
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = CN_IDX_PROC;
+        if libc::bind(
+            fd,
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        ) < 0
+        {
+            return close_on_error(fd);
+        }
+        // a receive timeout lets the listener thread notice when it has to stop
+        let timeout = libc::timeval {
+            tv_sec:  1,
+            tv_usec: 0,
+        };
+        if libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            &timeout as *const libc::timeval as *const libc::c_void,
+            std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+        ) < 0
+        {
+            return close_on_error(fd);
+        }
+        let message = listen_message();
+        if libc::send(
+            fd,
+            message.as_ptr() as *const libc::c_void,
+            message.len(),
+            0,
+        ) < 0
+        {
+            return close_on_error(fd);
+        }
+        Ok(fd)
+    }
+}
+
+/// The events received and not read yet
+type Received = Arc<(Mutex<Vec<KernelEvent>>, Condvar)>;
+
+/// A thread receiving the proc connector events and another one reading their
+/// processes and queuing them, the kernel drops the events that are not
+/// received fast enough
+struct Listener {
+    stop:    Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Listener {
+    fn start(
+        root: ProcRoot,
+        queue: Arc<Mutex<Vec<Update>>>,
+        notify: Arc<Notify>,
+    ) -> io::Result<Self> {
+        let fd = open_socket()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let received = Received::default();
+        let receiver = match Self::receive(fd, stop.clone(), received.clone()) {
+            Ok(receiver) => receiver,
+            Err(e) => {
+                // SAFETY: the thread was never started so we still own the file descriptor
+                unsafe { libc::close(fd) };
+                return Err(e);
+            },
+        };
+        // once started, dropping the listener stops the receiver and closes the socket
+        let mut listener = Self {
+            stop,
+            threads: vec![receiver],
+        };
+        let reader = Self::read(root, listener.stop.clone(), received, queue, notify)?;
+        listener.threads.push(reader);
+        Ok(listener)
+    }
+
+    fn read(
+        root: ProcRoot,
+        stop: Arc<AtomicBool>,
+        received: Received,
+        queue: Arc<Mutex<Vec<Update>>>,
+        notify: Arc<Notify>,
+    ) -> io::Result<JoinHandle<()>> {
+        std::thread::Builder::new().name("proc-connector-reader".to_string()).spawn(move || {
+            let (events, condvar) = &*received;
+            while !stop.load(Ordering::SeqCst) {
+                let events = {
+                    let mut events = events.lock();
+                    if events.is_empty() {
+                        // a timeout lets this thread notice when it has to stop
+                        condvar.wait_for(&mut events, Duration::from_secs(1));
+                    }
+                    std::mem::take(&mut *events)
+                };
+                if events.is_empty() {
+                    continue;
+                }
+                let updates = Update::read_all(&root, events);
+                if !updates.is_empty() {
+                    queue.lock().extend(updates);
+                    notify.notify_one();
+                }
+            }
+        })
+    }
+
+    fn receive(
+        fd: libc::c_int,
+        stop: Arc<AtomicBool>,
+        received: Received,
+    ) -> io::Result<JoinHandle<()>> {
+        std::thread::Builder::new().name("proc-connector".to_string()).spawn(move || {
+            let mut buffer = vec![0u8; RECV_BUFFER_LEN];
+            while !stop.load(Ordering::SeqCst) {
+                // SAFETY: the buffer outlives the call and its length is given
+                let len = unsafe {
+                    libc::recv(
+                        fd,
+                        buffer.as_mut_ptr() as *mut libc::c_void,
+                        buffer.len(),
+                        0,
+                    )
+                };
+                if len < 0 {
+                    let err = io::Error::last_os_error();
+                    match err.raw_os_error() {
+                        Some(libc::EAGAIN) | Some(libc::EINTR) => {},
+                        // the kernel dropped some events, the next scan reconciles them
+                        Some(libc::ENOBUFS) => warn!("Lost proc connector events"),
+                        _ => {
+                            warn!("Stopping the proc connector listener {err}");
+                            break;
+                        },
+                    }
+                    continue;
+                }
+                let events = parse_events(&buffer[..len as usize]);
+                if !events.is_empty() {
+                    let (received, condvar) = &*received;
+                    received.lock().extend(events);
+                    condvar.notify_one();
+                }
+            }
+            // SAFETY: the file descriptor is only used by this thread
+            unsafe { libc::close(fd) };
+        })
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A probe fed in real time by the kernel proc connector, the `/proc` scan of
+/// an acquisition is still used to reconcile (lost events, processes that
+/// could not be read when the event arrived)
+///
+/// Note: if the proc connector is not available (missing CAP_NET_ADMIN, not
+/// compiled in the kernel), this behaves like the manual probe
+pub struct NetlinkProbe {
+    pub(crate) cache: UnixProcessCache,
+    pub(crate) root:  ProcRoot,
+    queue:            Arc<Mutex<Vec<Update>>>,
+    notify:           Arc<Notify>,
+    listener:         Option<Listener>,
+    // the updates applied since a scan started, replayed on its processes
+    since_scan:       Mutex<Option<Vec<Update>>>,
+}
+
+impl Default for NetlinkProbe {
+    fn default() -> Self {
+        Self::new(ProcRoot::default())
+    }
+}
+
+impl NetlinkProbe {
+    /// How often `/proc` is scanned by default to reconcile the lost events,
+    /// see [`crate::UnixProbe::default_interval_ms`]
+    pub const RECONCILE_INTERVAL_MS: u64 = 60_000;
+
+    /// Note: the events are about the processes of the kernel we run on, the
+    /// root should be its `/proc` (or a mount of it)
+    pub fn new(root: ProcRoot) -> Self {
+        let queue = Arc::new(Mutex::new(vec![]));
+        let notify = Arc::new(Notify::new());
+        let listener = match Listener::start(root.clone(), queue.clone(), notify.clone()) {
+            Ok(listener) => Some(listener),
+            Err(e) => {
+                warn!("Could not subscribe to the proc connector, falling back to scans {e}");
+                None
+            },
+        };
+        Self {
+            cache: Default::default(),
+            root,
+            queue,
+            notify,
+            listener,
+            since_scan: Default::default(),
+        }
+    }
+
+    /// Whether the kernel events are received
+    pub fn is_listening(&self) -> bool {
+        self.listener.is_some()
+    }
+
+    fn kernel_events(&self) -> Option<Arc<Notify>> {
+        self.listener.as_ref().map(|_| self.notify.clone())
+    }
+
+    /// Note: the processes were already read, this only touches memory
+    fn drain_kernel_events(&mut self) -> usize {
+        let updates = std::mem::take(&mut *self.queue.lock());
+        for update in &updates {
+            match update {
+                Update::Read(process) => self.cache.upsert(process.clone()),
+                // a process read before is better than this
+                Update::Unreadable(process) => {
+                    let identity = process.identity();
+                    if !self.cache.inner().with_pid(process.pid).any(|x| x.identity() == identity) {
+                        self.cache.upsert(process.clone());
+                    }
+                },
+                Update::Exit(pid) => {
+                    // only one living process can hold a pid, so this is the most recent one
+                    let identity = self.cache.inner().with_pid(*pid).last().map(|x| x.identity());
+                    if let Some(identity) = identity {
+                        self.cache.remove(identity);
+                    }
+                },
+            }
+        }
+        let count = updates.len();
+        if let Some(since_scan) = self.since_scan.lock().as_mut() {
+            since_scan.extend(updates);
+        }
+        count
+    }
+
+    fn scan_started(&self) {
+        *self.since_scan.lock() = Some(vec![]);
+    }
+
+    /// Apply the updates of the scan to its processes before they replace the
+    /// cached ones, the scan may have read a process before its update (or not
+    /// at all)
+    ///
+    /// Note: the events were already sent when the updates were applied, the
+    /// cache ends up as it was before the swap for these processes
+    fn replay_kernel_events(&self, processes: Vec<UnixProcess>) -> Vec<UnixProcess> {
+        let Some(updates) = self.since_scan.lock().take() else {
+            return processes;
+        };
+        let mut processes =
+            processes.into_iter().map(|x| (x.identity(), x)).collect::<BTreeMap<_, _>>();
+        let last = |processes: &BTreeMap<ProcessIdentity, _>, pid| {
+            processes.range(ProcessIdentity::of_pid(pid)).next_back().map(|(&x, _)| x)
+        };
+        for update in updates {
+            match update {
+                // the process read is the most recent of its pid
+                Update::Read(process) => {
+                    while let Some(identity) = last(&processes, process.pid) {
+                        processes.remove(&identity);
+                    }
+                    processes.insert(process.identity(), process);
+                },
+                Update::Unreadable(process) => {
+                    processes.entry(process.identity()).or_insert(process);
+                },
+                Update::Exit(pid) => {
+                    if let Some(identity) = last(&processes, pid) {
+                        processes.remove(&identity);
+                    }
+                },
+            }
+        }
+        processes.into_values().collect()
+    }
+}
+
+impl_unix_probe!(NetlinkProbe, UnixProcess::from_manual_at, event_driven);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(what: u32, data: [u32; 4]) -> Vec<u8> {
+        let mut buffer = listen_message().to_vec();
+        let mut event = vec![0u8; PROC_EVENT_HEADER_LEN + 16];
+        NativeEndian::write_u32(&mut event[0..4], what);
+        for (i, x) in data.iter().enumerate() {
+            let start = PROC_EVENT_HEADER_LEN + i * 4;
+            NativeEndian::write_u32(&mut event[start..start + 4], *x);
+        }
+        buffer.truncate(NLMSG_HEADER_LEN + CN_MSG_HEADER_LEN);
+        buffer.extend(event);
+        let len = buffer.len();
+        NativeEndian::write_u32(&mut buffer[0..4], len as u32);
+        NativeEndian::write_u16(
+            &mut buffer[NLMSG_HEADER_LEN + 16..NLMSG_HEADER_LEN + 18],
+            32,
+        );
+        buffer
+    }
+
+    #[test_log::test]
+    fn test_parse_events() {
+        let mut buffer = message(PROC_EVENT_FORK, [1, 1, 42, 42]);
+        // a thread creation is ignored
+        buffer.extend(message(PROC_EVENT_FORK, [42, 42, 43, 42]));
+        buffer.extend(message(PROC_EVENT_EXEC, [42, 42, 0, 0]));
+        buffer.extend(message(PROC_EVENT_EXIT, [42, 42, 0, 0]));
+        assert_eq!(
+            parse_events(&buffer),
+            vec![
+                KernelEvent::Fork {
+                    parent: 1,
+                    child:  42,
+                },
+                KernelEvent::Exec(42),
+                KernelEvent::Exit(42),
+            ]
+        );
+    }
+
+    #[test_log::test]
+    fn test_read_events() {
+        let root = ProcRoot::default();
+        let pid = std::process::id();
+        let mut child = std::process::Command::new("true").spawn().expect("works");
+        let exited = child.id();
+        child.wait().expect("works");
+        let updates = Update::read_all(
+            &root,
+            vec![
+                KernelEvent::Fork {
+                    parent: 1,
+                    child:  pid,
+                },
+                KernelEvent::Exec(pid),
+                // gone before it is read
+                KernelEvent::Exec(exited),
+                KernelEvent::Exit(exited),
+            ],
+        );
+        let updates = updates
+            .iter()
+            .map(|x| match x {
+                Update::Read(process) => ("read", process.pid),
+                Update::Unreadable(process) => ("unreadable", process.pid),
+                Update::Exit(pid) => ("exit", *pid),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(updates, [("read", pid), ("exit", exited)]);
+        // the placeholder of a process is the same process for the next scan
+        assert_eq!(
+            UnixProcess::from_stat(&root, pid).expect("works").identity(),
+            UnixProcess::from_manual_at(&root, pid).expect("works").identity()
+        );
+    }
+
+    #[test_log::test]
+    fn test_replay_kernel_events() {
+        use crate::process::{Collection, ProcessEvent, ProcessProbe};
+        let mut probe = NetlinkProbe {
+            cache:      Default::default(),
+            root:       ProcRoot::default(),
+            queue:      Default::default(),
+            notify:     Default::default(),
+            listener:   None,
+            since_scan: Default::default(),
+        };
+        let mut channel = probe.obtain_channel();
+        // the scan read 10 and 11, 10 exits and 12 is forked before it is published
+        let _collector = probe.collector(Default::default());
+        probe.queue.lock().extend([
+            Update::Exit(10),
+            Update::Unreadable(UnixProcess::placeholder(12, 11, 0)),
+        ]);
+        assert_eq!(probe.apply_pending_events(), 2);
+        let processes = vec![
+            UnixProcess::placeholder(10, 1, 0),
+            UnixProcess::placeholder(11, 1, 0),
+        ];
+        let published = probe.publish_processes(Collection {
+            processes,
+            timing: Default::default(),
+        });
+        assert_eq!(
+            published.iter().map(|x| x.pid).collect::<Vec<_>>(),
+            [11, 12]
+        );
+        let mut new = vec![];
+        while let Ok(event) = channel.try_recv() {
+            match event {
+                ProcessEvent::New(process) => new.push(process.pid),
+                event => panic!("Unexpected {event:?}"),
+            }
+        }
+        assert_eq!(new, [12, 11]);
+    }
+}
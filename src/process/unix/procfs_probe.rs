@@ -1,37 +1,110 @@
 use crate::{
     impl_unix_probe,
     process::{
-        unix::{UnixProcess, UnixProcessCache},
+        unix::{
+            read_cgroup,
+            read_environ_if_enabled,
+            read_exe,
+            read_exe_hash_if_enabled,
+            read_fds_if_enabled,
+            read_lsm_label,
+            read_mappings_if_enabled,
+            read_namespaces,
+            read_status,
+            read_threads_if_enumerated,
+            start_time_millis,
+            Capabilities,
+            CapabilitySet,
+            CpuTimes,
+            Credentials,
+            IdSet,
+            Memory,
+            ProcRoot,
+            Security,
+            UnixProcess,
+            UnixProcessCache,
+        },
         Pid,
     },
-    ProcessServerError,
     ProcessServerResult,
 };
 use tracing::debug;
 
 impl UnixProcess {
     pub fn from_procfs(pid: Pid) -> ProcessServerResult<Self> {
+        Self::from_procfs_at(&ProcRoot::default(), pid)
+    }
+
+    pub fn from_procfs_at(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Self> {
         debug!("Calling procfs for pid {}", pid);
-        let process = procfs::process::Process::new(pid as i32)?;
+        let process = procfs::process::Process::new_with_root(root.process_path(pid))?;
 
         let stat = process.stat()?;
         let status = process.status()?;
-        let owner_name = users::get_user_by_uid(status.ruid)
-            .ok_or(ProcessServerError::UserNotFound(status.ruid))?
-            .name()
-            .to_owned();
+        let owner_name = root.user_name(status.ruid)?;
+        let credentials = Credentials::new(
+            root,
+            IdSet {
+                real:      status.ruid,
+                effective: status.euid,
+                saved:     status.suid,
+                fs:        status.fuid,
+            },
+            IdSet {
+                real:      status.rgid,
+                effective: status.egid,
+                saved:     status.sgid,
+                fs:        status.fgid,
+            },
+            status.groups.iter().map(|&x| x as u32).collect(),
+        );
+        let capabilities = Capabilities {
+            inheritable: CapabilitySet(status.capinh),
+            permitted:   CapabilitySet(status.capprm),
+            effective:   CapabilitySet(status.capeff),
+            bounding:    CapabilitySet(status.capbnd.unwrap_or_default()),
+            ambient:     CapabilitySet(status.capamb.unwrap_or_default()),
+        };
+        // from the text, the crate does not parse the number of seccomp filters
+        let security = Security {
+            lsm_label: read_lsm_label(root, pid),
+            ..Security::from_status(&read_status(root, pid)?)
+        };
         let mut args = process.cmdline()?;
-        let _executable = args.remove(0);
+        if !args.is_empty() {
+            let _executable = args.remove(0);
+        }
+        // not through the crate so the probes agree on the unreadable ones
+        let (exe, exe_status) = read_exe(root, pid);
+        let (namespaces, nspid) = read_namespaces(root, pid)?;
         Ok(Self {
             pid,
             ppid: stat.ppid as Pid,
+            start_time: start_time_millis(root.boot_time()?, stat.starttime),
             name: stat.comm,
             owner_id: status.ruid,
             owner_name,
-            exe: process.exe()?,
+            exe,
+            exe_status,
             cwd: process.cwd()?,
             cmdline: args.join(" "),
             args,
+            credentials,
+            capabilities,
+            security,
+            cpu_times: CpuTimes::new(stat.utime, stat.stime),
+            cpu_usage: None,
+            memory: Memory::read(root, pid)?,
+            threads: read_threads_if_enumerated(root, pid),
+            fds: read_fds_if_enabled(root, pid),
+            sockets: None,
+            environ: read_environ_if_enabled(root, pid),
+            mappings: read_mappings_if_enabled(root, pid),
+            exe_sha256: read_exe_hash_if_enabled(root, pid),
+            cgroup: read_cgroup(root, pid),
+            namespaces,
+            nspid,
+            fingerprint: None,
         })
     }
 }
@@ -39,9 +112,19 @@ impl UnixProcess {
 #[derive(Default)]
 pub struct ProcfsProbe {
     pub(crate) cache: UnixProcessCache,
+    pub(crate) root:  ProcRoot,
+}
+
+impl ProcfsProbe {
+    pub fn new(root: ProcRoot) -> Self {
+        Self {
+            cache: Default::default(),
+            root,
+        }
+    }
 }
 
-impl_unix_probe!(ProcfsProbe, UnixProcess::from_procfs);
+impl_unix_probe!(ProcfsProbe, UnixProcess::from_procfs_at);
 
 
 #[cfg(test)]
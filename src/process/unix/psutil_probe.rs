@@ -1,38 +1,94 @@
 use crate::{
     impl_unix_probe,
     process::{
-        unix::{UnixProcess, UnixProcessCache},
+        unix::{
+            read_cgroup,
+            read_environ_if_enabled,
+            read_exe,
+            read_exe_hash_if_enabled,
+            read_fds_if_enabled,
+            read_lsm_label,
+            read_mappings_if_enabled,
+            read_namespaces,
+            read_threads_if_enumerated,
+            start_time_millis,
+            Capabilities,
+            CpuTimes,
+            Credentials,
+            Memory,
+            ProcRoot,
+            Security,
+            UnixProcess,
+            UnixProcessCache,
+        },
         Pid,
     },
-    ProcessServerError,
     ProcessServerResult,
 };
-use psutil::process::os::linux::ProcessExt;
+use psutil::process::os::linux::{ProcfsStat, ProcfsStatus};
+use std::str::FromStr;
 use tracing::debug;
 
 impl UnixProcess {
     pub fn from_psutil(pid: Pid) -> ProcessServerResult<Self> {
-        debug!("Calling procfs for pid {}", pid);
-        let process = psutil::process::Process::new(pid)?;
-
-        let stat = process.procfs_stat()?;
-        let status = process.procfs_status()?;
-        let owner_name = users::get_user_by_uid(status.uid[0])
-            .ok_or(ProcessServerError::UserNotFound(status.uid[0]))?
-            .name()
-            .to_owned();
-        let mut args = process.cmdline_vec()?.unwrap_or(vec!["kernel".to_string()]);
-        let _executable = args.remove(0);
+        Self::from_psutil_at(&ProcRoot::default(), pid)
+    }
+
+    /// Note: psutil only reads `/proc`, so the files are read from the root and
+    /// only parsed by psutil
+    pub fn from_psutil_at(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Self> {
+        debug!("Calling psutil for pid {}", pid);
+        let process_path = root.process_path(pid);
+
+        let stat = ProcfsStat::from_str(&std::fs::read_to_string(process_path.join("stat"))?)?;
+        let status_content = std::fs::read_to_string(process_path.join("status"))?;
+        let status = ProcfsStatus::from_str(&status_content)?;
+        let owner_name = root.user_name(status.uid[0])?;
+        // Note: psutil does not parse the supplementary groups
+        let credentials = Credentials::from_status(root, &status_content)?;
+        // Note: psutil does not parse the capabilities either
+        let capabilities = Capabilities::from_status(&status_content)?;
+        let security = Security {
+            lsm_label: read_lsm_label(root, pid),
+            ..Security::from_status(&status_content)
+        };
+        // the arguments are separated and terminated by a nul byte
+        let cmdline = std::fs::read_to_string(process_path.join("cmdline"))?;
+        let mut args = cmdline.split_terminator('\0').map(String::from).collect::<Vec<_>>();
+        // kernel threads and zombies have no command line
+        if !args.is_empty() {
+            let _executable = args.remove(0);
+        }
+        let (exe, exe_status) = read_exe(root, pid);
+        let (namespaces, nspid) = read_namespaces(root, pid)?;
         Ok(Self {
             pid,
             ppid: stat.ppid.unwrap_or(0) as Pid,
+            start_time: start_time_millis(root.boot_time()?, stat.starttime_ticks as u64),
             name: stat.comm,
             owner_id: status.uid[0],
             owner_name,
-            exe: process.exe()?,
-            cwd: process.cwd()?,
+            exe,
+            exe_status,
+            cwd: process_path.join("cwd").read_link()?,
             cmdline: args.join(" "),
             args,
+            credentials,
+            capabilities,
+            security,
+            cpu_times: CpuTimes::new(stat.utime_ticks, stat.stime_ticks),
+            cpu_usage: None,
+            memory: Memory::read(root, pid)?,
+            threads: read_threads_if_enumerated(root, pid),
+            fds: read_fds_if_enabled(root, pid),
+            sockets: None,
+            environ: read_environ_if_enabled(root, pid),
+            mappings: read_mappings_if_enabled(root, pid),
+            exe_sha256: read_exe_hash_if_enabled(root, pid),
+            cgroup: read_cgroup(root, pid),
+            namespaces,
+            nspid,
+            fingerprint: None,
         })
     }
 }
@@ -40,9 +96,19 @@ impl UnixProcess {
 #[derive(Default)]
 pub struct PsutilProbe {
     pub(crate) cache: UnixProcessCache,
+    pub(crate) root:  ProcRoot,
+}
+
+impl PsutilProbe {
+    pub fn new(root: ProcRoot) -> Self {
+        Self {
+            cache: Default::default(),
+            root,
+        }
+    }
 }
 
-impl_unix_probe!(PsutilProbe, UnixProcess::from_psutil);
+impl_unix_probe!(PsutilProbe, UnixProcess::from_psutil_at);
 
 
 #[cfg(test)]
@@ -0,0 +1,559 @@
+use crate::{
+    process::{unix::exe_hash::ExeHashes, AcquisitionOptions, Pid},
+    ProcessServerError,
+    ProcessServerResult,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+pub(crate) const PROC_PATH: &str = "/proc";
+const KERNEL_STAT: &str = "stat";
+
+/// Where the unix probes read the processes from
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnixProbeConfig {
+    /// The procfs mount, e.g. the host one mounted at `/host/proc` in a
+    /// container
+    #[serde(default = "default_proc_root")]
+    pub proc_root:          PathBuf,
+    /// A passwd file to resolve the user names matching the `proc_root`, the
+    /// users of the system are used when `None`
+    #[serde(default)]
+    pub passwd:             Option<PathBuf>,
+    /// A group file to resolve the group names, like `passwd`
+    #[serde(default)]
+    pub group:              Option<PathBuf>,
+    /// How many threads read the processes, 1 reads them sequentially
+    #[serde(default = "default_threads")]
+    pub threads:            usize,
+    /// Only read again the processes that changed since the last acquisition,
+    /// see [`ProcRoot::incremental`]
+    #[serde(default)]
+    pub incremental:        bool,
+    /// Read the proportional memory of the processes, see
+    /// [`ProcRoot::smaps_rollup`]
+    #[serde(default)]
+    pub smaps_rollup:       bool,
+    /// Read the threads of the processes, see [`ProcRoot::enumerate_threads`]
+    #[serde(default)]
+    pub enumerate_threads:  bool,
+    /// Read the open file descriptors of the processes, see [`ProcRoot::fds`]
+    #[serde(default)]
+    pub fds:                bool,
+    /// Read the environment of the processes, see [`ProcRoot::environ`]
+    #[serde(default)]
+    pub environ:            bool,
+    /// Read the mapped files of the processes, see [`ProcRoot::maps`]
+    #[serde(default)]
+    pub maps:               bool,
+    /// Hash the executables of the processes, see [`ProcRoot::exe_hash`]
+    #[serde(default)]
+    pub exe_hash:           bool,
+    /// The bigger executables are not hashed
+    #[serde(default = "default_exe_hash_max_size")]
+    pub exe_hash_max_size:  u64,
+    /// The time an acquisition can spend hashing, the executables left are
+    /// hashed by the next ones
+    #[serde(default = "default_exe_hash_budget_ms")]
+    pub exe_hash_budget_ms: u64,
+}
+
+fn default_proc_root() -> PathBuf {
+    PathBuf::from(PROC_PATH)
+}
+
+fn default_threads() -> usize {
+    1
+}
+
+fn default_exe_hash_max_size() -> u64 {
+    256 * 1024 * 1024
+}
+
+fn default_exe_hash_budget_ms() -> u64 {
+    2000
+}
+
+impl Default for UnixProbeConfig {
+    fn default() -> Self {
+        Self {
+            proc_root:          default_proc_root(),
+            passwd:             None,
+            group:              None,
+            threads:            default_threads(),
+            incremental:        false,
+            smaps_rollup:       false,
+            enumerate_threads:  false,
+            fds:                false,
+            environ:            false,
+            maps:               false,
+            exe_hash:           false,
+            exe_hash_max_size:  default_exe_hash_max_size(),
+            exe_hash_budget_ms: default_exe_hash_budget_ms(),
+        }
+    }
+}
+
+/// A procfs mount along with what is shared by all its processes (boot time,
+/// user names)
+///
+/// Note: this is cheap to clone, the collectors get their own copy
+#[derive(Debug, Clone)]
+pub struct ProcRoot {
+    path:              PathBuf,
+    boot_time:         Option<u64>,
+    users:             Option<Arc<HashMap<u32, OsString>>>,
+    groups:            Option<Arc<HashMap<u32, OsString>>>,
+    threads:           usize,
+    incremental:       bool,
+    smaps_rollup:      bool,
+    enumerate_threads: bool,
+    fds:               bool,
+    environ:           bool,
+    maps:              bool,
+    exe_hashes:        Option<Arc<ExeHashes>>,
+    // set for the reads of an acquisition, see `ProcRoot::start_acquisition`
+    exe_hash_deadline: Option<Instant>,
+}
+
+impl Default for ProcRoot {
+    fn default() -> Self {
+        Self::new(&UnixProbeConfig::default())
+    }
+}
+
+impl ProcRoot {
+    /// Note: the boot time and the passwd file are only read once here, if they
+    /// can't be read the processes will fail to be read (resp. the system users
+    /// are used)
+    pub fn new(config: &UnixProbeConfig) -> Self {
+        let boot_time = match read_boot_time(&config.proc_root) {
+            Ok(btime) => Some(btime),
+            Err(e) => {
+                warn!("Could not read the boot time {e}");
+                None
+            },
+        };
+        let users = config.passwd.as_ref().and_then(|path| match read_names(path) {
+            Ok(users) => Some(Arc::new(users)),
+            Err(e) => {
+                warn!(
+                    "Could not read the passwd file {:?}, using the system users {e}",
+                    path
+                );
+                None
+            },
+        });
+        let groups = config.group.as_ref().and_then(|path| match read_names(path) {
+            Ok(groups) => Some(Arc::new(groups)),
+            Err(e) => {
+                warn!(
+                    "Could not read the group file {:?}, using the system groups {e}",
+                    path
+                );
+                None
+            },
+        });
+        Self {
+            path:              config.proc_root.clone(),
+            boot_time,
+            users,
+            groups,
+            threads:           config.threads.max(1),
+            incremental:       config.incremental,
+            smaps_rollup:      config.smaps_rollup,
+            enumerate_threads: config.enumerate_threads,
+            fds:               config.fds,
+            environ:           config.environ,
+            maps:              config.maps,
+            exe_hashes:        config.exe_hash.then(|| {
+                Arc::new(ExeHashes::new(
+                    config.exe_hash_max_size,
+                    Duration::from_millis(config.exe_hash_budget_ms),
+                ))
+            }),
+            exe_hash_deadline: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn process_path(&self, pid: Pid) -> PathBuf {
+        self.path.join(pid.to_string())
+    }
+
+    /// How many threads read the processes of this root
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Whether the processes already read are reused when they did not change
+    ///
+    /// A process is reused when its pid, start time, comm, ppid, code and
+    /// arguments addresses (the exec generation), owner, cwd and arguments are
+    /// the same, this only needs `/proc/[pid]/stat`, `/proc/[pid]/cmdline` and
+    /// two syscalls instead of reading and parsing all its files. The rest is
+    /// only read again when the process ran since (its CPU times changed).
+    ///
+    /// Note: a reused process is the same as a full read except for what can
+    /// change without it running (or within less than a clock tick of CPU
+    /// time): its memory (reclaim, swap), its cgroup and LSM label (moved or
+    /// relabeled by another process) and the status of its executable
+    /// (replaced or removed)
+    pub fn incremental(&self) -> bool {
+        self.incremental
+    }
+
+    /// Whether the PSS and USS of the processes are read, this walks all the
+    /// mappings of a process in the kernel so it is much slower than the
+    /// other memory counters
+    pub fn smaps_rollup(&self) -> bool {
+        self.smaps_rollup
+    }
+
+    /// The root for the reads of an acquisition starting now, the exe hashes
+    /// stop once their budget is spent
+    ///
+    /// Note: the processes read outside an acquisition (by the netlink probe)
+    /// have no budget
+    pub(crate) fn start_acquisition(&self) -> Self {
+        Self {
+            exe_hash_deadline: self.exe_hashes().map(|x| Instant::now() + x.budget()),
+            ..self.clone()
+        }
+    }
+
+    /// The root for a single acquisition
+    pub(crate) fn with_options(mut self, options: AcquisitionOptions) -> Self {
+        if let Some(smaps_rollup) = options.smaps_rollup {
+            self.smaps_rollup = smaps_rollup;
+        }
+        self
+    }
+
+    /// Whether the threads of the processes are read from `/proc/[pid]/task`,
+    /// this reads a few files per thread
+    ///
+    /// Note: unlike [`ProcRoot::threads`] this has nothing to do with how the
+    /// processes are read
+    pub fn enumerate_threads(&self) -> bool {
+        self.enumerate_threads
+    }
+
+    /// Whether the open file descriptors of the processes are read from
+    /// `/proc/[pid]/fd` and `/proc/[pid]/fdinfo`, this reads two files per fd
+    pub fn fds(&self) -> bool {
+        self.fds
+    }
+
+    /// Whether the environment of the processes is read from
+    /// `/proc/[pid]/environ`, it often holds secrets so this is opt-in
+    pub fn environ(&self) -> bool {
+        self.environ
+    }
+
+    /// Whether the files mapped by the processes (libraries, memfds) are read
+    /// from `/proc/[pid]/maps`, a process of a big program has thousands of
+    /// mappings
+    pub fn maps(&self) -> bool {
+        self.maps
+    }
+
+    /// Whether the SHA-256 of the executables of the processes are computed,
+    /// once per version of a file whatever the number of processes running it
+    pub fn exe_hash(&self) -> bool {
+        self.exe_hashes.is_some()
+    }
+
+    pub(crate) fn exe_hashes(&self) -> Option<&ExeHashes> {
+        self.exe_hashes.as_deref()
+    }
+
+    pub(crate) fn exe_hash_deadline(&self) -> Option<Instant> {
+        self.exe_hash_deadline
+    }
+
+    /// The boot time in seconds since the epoch
+    pub fn boot_time(&self) -> ProcessServerResult<u64> {
+        self.boot_time.ok_or(ProcessServerError::InvalidUnixStat(
+            "Unknown boot time".to_string(),
+        ))
+    }
+
+    pub fn user_name(&self, uid: u32) -> ProcessServerResult<OsString> {
+        match &self.users {
+            Some(users) => users.get(&uid).cloned(),
+            None => users::get_user_by_uid(uid).map(|user| user.name().to_owned()),
+        }
+        .ok_or(ProcessServerError::UserNotFound(uid))
+    }
+
+    pub fn group_name(&self, gid: u32) -> Option<OsString> {
+        match &self.groups {
+            Some(groups) => groups.get(&gid).cloned(),
+            None => users::get_group_by_gid(gid).map(|group| group.name().to_owned()),
+        }
+    }
+}
+
+/// Read the boot time in seconds since the epoch from the `btime` line of
+/// `/proc/stat`
+fn read_boot_time(proc_root: &Path) -> ProcessServerResult<u64> {
+    // See definition in https://man7.org/linux/man-pages/man5/proc.5.html at `/proc/stat`
+    let mut file = File::open(proc_root.join(KERNEL_STAT))?;
+    let mut content = String::new();
+    let _ = file.read_to_string(&mut content)?;
+    let btime = content.lines().find_map(|line| line.strip_prefix("btime ")).ok_or(
+        ProcessServerError::InvalidUnixStat("Missing btime in /proc/stat".to_string()),
+    )?;
+    btime.trim().parse().map_err(|e| {
+        ProcessServerError::InvalidUnixStat(format!("The btime field is not u64 size: {e}"))
+    })
+}
+
+/// Read the `name:password:id:...` lines of a passwd or group file
+///
+/// Note: the invalid lines are ignored, as the system tools do
+fn read_names(path: &Path) -> ProcessServerResult<HashMap<u32, OsString>> {
+    // See definition in https://man7.org/linux/man-pages/man5/passwd.5.html and
+    // https://man7.org/linux/man-pages/man5/group.5.html
+    let mut file = File::open(path)?;
+    let mut content = vec![];
+    let _ = file.read_to_end(&mut content)?;
+    let users = content
+        .split(|x| *x == b'\n')
+        .filter(|line| !line.starts_with(b"#"))
+        .filter_map(|line| {
+            use std::os::unix::ffi::OsStrExt;
+            let mut fields = line.split(|x| *x == b':');
+            let name = fields.next()?;
+            let uid = std::str::from_utf8(fields.nth(1)?).ok()?.parse().ok()?;
+            Some((uid, std::ffi::OsStr::from_bytes(name).to_owned()))
+        })
+        .collect();
+    Ok(users)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        process::{ProcessProbe, UnixManualProbe, UnixProcess, UnixProcfsProbe, UnixPsutilProbe},
+        StaticProcess,
+    };
+    use std::os::unix::fs::symlink;
+
+    /// A proc root holding copies of the current process, owned by `fixture`
+    fn fixture(directory: &Path, pids: &[Pid]) -> UnixProbeConfig {
+        for pid in pids {
+            let process = directory.join("proc").join(pid.to_string());
+            std::fs::create_dir_all(&process).expect("works");
+            for file in ["stat", "status", "statm", "cmdline"] {
+                std::fs::copy(format!("/proc/self/{file}"), process.join(file)).expect("works");
+            }
+            symlink(std::env::current_exe().expect("works"), process.join("exe")).expect("works");
+            symlink(std::env::current_dir().expect("works"), process.join("cwd")).expect("works");
+        }
+        std::fs::copy("/proc/stat", directory.join("proc").join(KERNEL_STAT)).expect("works");
+        let uid = users::get_current_uid();
+        std::fs::write(
+            directory.join("passwd"),
+            format!("# comment\nfixture:x:{uid}:{uid}::/:/bin/sh\n"),
+        )
+        .expect("works");
+        UnixProbeConfig {
+            proc_root:          directory.join("proc"),
+            passwd:             Some(directory.join("passwd")),
+            group:              None,
+            threads:            1,
+            incremental:        false,
+            smaps_rollup:       false,
+            enumerate_threads:  false,
+            fds:                false,
+            environ:            false,
+            maps:               false,
+            exe_hash:           false,
+            exe_hash_max_size:  0,
+            exe_hash_budget_ms: 0,
+        }
+    }
+
+    /// Add a clock tick to the user time of a fixture process, as if it ran
+    fn run(directory: &Path, pid: Pid) {
+        let stat = directory.join("proc").join(pid.to_string()).join("stat");
+        let content = std::fs::read_to_string(&stat).expect("works");
+        let end = content.rfind(')').expect("works") + 1;
+        let mut fields = content[end..].split(' ').map(String::from).collect::<Vec<_>>();
+        // fields[0] is empty (the space after the comm) and fields[12] is utime
+        fields[12] = (fields[12].parse::<u64>().expect("works") + 1).to_string();
+        std::fs::write(&stat, format!("{}{}", &content[..end], fields.join(" "))).expect("works");
+    }
+
+    #[test_log::test]
+    fn test_fixture_root() {
+        let directory =
+            std::env::temp_dir().join(format!("process_server_root_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let pid = std::process::id();
+        let root = ProcRoot::new(&fixture(&directory, &[pid]));
+        for process in [
+            UnixProcess::from_manual_at(&root, pid).expect("works"),
+            UnixProcess::from_procfs_at(&root, pid).expect("works"),
+            UnixProcess::from_psutil_at(&root, pid).expect("works"),
+        ] {
+            assert_eq!(process.pid(), pid);
+            assert_eq!(process.owner_name(), "fixture");
+            assert!(process.memory().rss > 0);
+        }
+        let mut probe = UnixManualProbe::new(root.clone());
+        assert_eq!(probe.collect_processes().expect("works").len(), 1);
+        let mut probe = UnixProcfsProbe::new(root.clone());
+        assert_eq!(probe.collect_processes().expect("works").len(), 1);
+        let mut probe = UnixPsutilProbe::new(root);
+        assert_eq!(probe.collect_processes().expect("works").len(), 1);
+        std::fs::remove_dir_all(&directory).expect("works");
+    }
+
+    #[test_log::test]
+    fn test_parallel_order() {
+        let directory =
+            std::env::temp_dir().join(format!("process_server_threads_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut config = fixture(&directory, &[3, 1, 42, 7, 12, 5]);
+        let sequential = crate::process::unix::collect_root(
+            &ProcRoot::new(&config),
+            UnixProcess::from_manual_at,
+            HashMap::new(),
+        )
+        .expect("works");
+        config.threads = 4;
+        let parallel = crate::process::unix::collect_root(
+            &ProcRoot::new(&config),
+            UnixProcess::from_manual_at,
+            HashMap::new(),
+        )
+        .expect("works");
+        std::fs::remove_dir_all(&directory).expect("works");
+        let pids =
+            |processes: &[UnixProcess]| processes.iter().map(|x| x.pid()).collect::<Vec<_>>();
+        assert_eq!(pids(&sequential.processes), vec![1, 3, 5, 7, 12, 42]);
+        assert_eq!(pids(&sequential.processes), pids(&parallel.processes));
+        assert_eq!(sequential.timing.threads, 1);
+        assert_eq!(parallel.timing.threads, 4);
+        assert_eq!(parallel.timing.failed, 0);
+    }
+
+    #[test_log::test]
+    fn test_incremental() {
+        let directory =
+            std::env::temp_dir().join(format!("process_server_incremental_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut config = fixture(&directory, &[1, 2, 3]);
+        config.incremental = true;
+        let uid = users::get_current_uid();
+        let passwd = std::fs::read_to_string(directory.join("passwd")).expect("works");
+        std::fs::write(
+            directory.join("passwd"),
+            format!("{passwd}other:x:{}:{uid}::/:/bin/sh\n", uid + 1),
+        )
+        .expect("works");
+        let mut probe = UnixManualProbe::new(ProcRoot::new(&config));
+        let timing = |probe: &UnixManualProbe| {
+            let cache = probe.process_cache().inner();
+            cache.current().and_then(|x| x.timing()).expect("works")
+        };
+        let processes = probe.collect_processes().expect("works");
+        assert!(processes.iter().all(|x| x.cpu_usage().is_none()));
+        assert_eq!(timing(&probe).reused, 0);
+        // the usage comes with the second acquisition, reused or not
+        let processes = probe.collect_processes().expect("works");
+        assert!(processes.iter().all(|x| x.cpu_usage().is_some()));
+        assert_eq!(timing(&probe).reused, 3);
+        // a chdir is caught by the fingerprint
+        let cwd = directory.join("proc").join("2").join("cwd");
+        std::fs::remove_file(&cwd).expect("works");
+        symlink(&directory, &cwd).expect("works");
+        let processes = probe.collect_processes().expect("works");
+        assert_eq!(processes[1].cwd(), directory);
+        assert_eq!(timing(&probe).reused, 2);
+        // a `setreuid` keeping the effective uid is not caught by the fingerprint, the
+        // owner of a reused process that ran follows its real uid anyway
+        let status = directory.join("proc").join("3").join("status");
+        let content = std::fs::read_to_string(&status).expect("works");
+        let content = content
+            .lines()
+            .map(|line| {
+                if line.starts_with("Uid:") {
+                    format!("Uid:\t{}\t{uid}\t{uid}\t{uid}", uid + 1)
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(&status, content + "\n").expect("works");
+        run(&directory, 3);
+        let processes = probe.collect_processes().expect("works");
+        assert_eq!(processes[2].owner_id(), (uid + 1).to_string());
+        assert_eq!(processes[2].owner_name(), "other");
+        assert_eq!(processes[1].owner_name(), "fixture");
+        assert_eq!(timing(&probe).reused, 3);
+        // so is a `setproctitle`
+        let cmdline = directory.join("proc").join("1").join("cmdline");
+        std::fs::write(&cmdline, b"renamed\0--title\0").expect("works");
+        let processes = probe.collect_processes().expect("works");
+        assert_eq!(processes[0].cmdline(), "renamed --title");
+        assert_eq!(timing(&probe).reused, 2);
+        std::fs::remove_dir_all(&directory).expect("works");
+    }
+
+    #[test_log::test]
+    fn test_incremental_identical() {
+        let directory =
+            std::env::temp_dir().join(format!("process_server_identical_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut config = fixture(&directory, &[1, 2]);
+        config.incremental = true;
+        let root = ProcRoot::new(&config);
+        let mut probe = UnixManualProbe::new(root.clone());
+        probe.collect_processes().expect("works");
+        // 1 is reused as is, 2 is reused but its files are read again
+        run(&directory, 2);
+        let processes =
+            probe.collect_processes().expect("works").into_iter().cloned().collect::<Vec<_>>();
+        let cache = probe.process_cache().inner();
+        assert_eq!(
+            cache.current().and_then(|x| x.timing()).expect("works").reused,
+            2
+        );
+        // only the usage (computed across acquisitions), the fingerprint (set by the
+        // incremental collection) and when the times were sampled can differ
+        let fields = |process: &UnixProcess, read: &UnixProcess| {
+            let mut process = process.clone();
+            process.cpu_usage = None;
+            process.fingerprint = None;
+            process.cpu_times.sampled_at = read.cpu_times.sampled_at;
+            format!("{process:#?}")
+        };
+        for process in processes {
+            let read = UnixProcess::from_manual_at(&root, process.pid()).expect("works");
+            let (reused, read) = (fields(&process, &read), fields(&read, &read));
+            assert_eq!(reused.lines().count(), read.lines().count());
+            for (reused, read) in reused.lines().zip(read.lines()) {
+                assert_eq!(reused, read, "pid {}", process.pid());
+            }
+        }
+        std::fs::remove_dir_all(&directory).expect("works");
+    }
+}
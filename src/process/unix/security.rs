@@ -0,0 +1,125 @@
+use crate::process::{unix::ProcRoot, Pid};
+
+const ATTR_CURRENT: &str = "attr/current";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeccompMode {
+    Disabled,
+    /// Only `read`, `write`, `_exit` and `sigreturn`
+    Strict,
+    /// Filtered by BPF programs
+    Filter,
+}
+
+impl SeccompMode {
+    /// The value of the `Seccomp:` line
+    pub(crate) fn from_value(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(SeccompMode::Disabled),
+            1 => Some(SeccompMode::Strict),
+            2 => Some(SeccompMode::Filter),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SeccompMode::Disabled => "disabled",
+            SeccompMode::Strict => "strict",
+            SeccompMode::Filter => "filter",
+        }
+    }
+}
+
+/// What confines a process beyond its credentials, see `/proc/[pid]/status`
+/// and `/proc/[pid]/attr/current`
+///
+/// Note: every field is `None` when unknown, the kernel may be too old or
+/// built without it (or the LSM may not expose a label)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Security {
+    pub seccomp:                  Option<SeccompMode>,
+    /// The number of attached filters, since Linux 5.9
+    pub seccomp_filters:          Option<u32>,
+    pub no_new_privs:             Option<bool>,
+    /// As worded by the kernel, e.g. `thread force mitigated` or `vulnerable`
+    pub speculation_store_bypass: Option<String>,
+    /// The SELinux context or AppArmor profile, e.g.
+    /// `system_u:system_r:httpd_t:s0` or `nginx (enforce)`
+    pub lsm_label:                Option<String>,
+}
+
+impl Security {
+    /// Everything but the label, an invalid value is unknown
+    pub(crate) fn from_status(status: &str) -> Self {
+        let value = |key: &str| {
+            status.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix(':')).map(str::trim)
+        };
+        Self {
+            seccomp:                  value("Seccomp")
+                .and_then(|x| x.parse().ok())
+                .and_then(SeccompMode::from_value),
+            seccomp_filters:          value("Seccomp_filters").and_then(|x| x.parse().ok()),
+            no_new_privs:             value("NoNewPrivs").and_then(|x| match x {
+                "0" => Some(false),
+                "1" => Some(true),
+                _ => None,
+            }),
+            speculation_store_bypass: value("Speculation_Store_Bypass").map(String::from),
+            lsm_label:                None,
+        }
+    }
+
+    /// Whether seccomp filters the process or an LSM profile other than
+    /// unconfined (AppArmor `unconfined`, SELinux `unconfined_t`) applies to
+    /// it, `None` when both are unknown
+    pub fn is_confined(&self) -> Option<bool> {
+        let seccomp = self.seccomp.map(|x| x != SeccompMode::Disabled);
+        let lsm = self
+            .lsm_label
+            .as_deref()
+            .map(|x| x != "unconfined" && !x.contains(":unconfined_t:"));
+        match (seccomp, lsm) {
+            (None, None) => None,
+            (seccomp, lsm) => Some(seccomp.unwrap_or(false) || lsm.unwrap_or(false)),
+        }
+    }
+}
+
+/// Read the LSM label of a process, `None` without an LSM (the read fails with
+/// `EINVAL`) or when not allowed
+pub(crate) fn read_lsm_label(root: &ProcRoot, pid: Pid) -> Option<String> {
+    let content = std::fs::read(root.process_path(pid).join(ATTR_CURRENT)).ok()?;
+    let label = String::from_utf8_lossy(&content);
+    let label = label.trim_end_matches(['\n', '\0']);
+    (!label.is_empty()).then(|| label.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_from_status() {
+        let status = concat!(
+            "NoNewPrivs:\t1\n",
+            "Seccomp:\t2\n",
+            "Seccomp_filters:\t3\n",
+            "Speculation_Store_Bypass:\tthread force mitigated\n",
+        );
+        let security = Security::from_status(status);
+        assert_eq!(security.seccomp, Some(SeccompMode::Filter));
+        assert_eq!(security.seccomp_filters, Some(3));
+        assert_eq!(security.no_new_privs, Some(true));
+        assert_eq!(
+            security.speculation_store_bypass.as_deref(),
+            Some("thread force mitigated")
+        );
+        assert_eq!(security.is_confined(), Some(true));
+
+        let security = Security::from_status("Seccomp:\t0\n");
+        assert_eq!(security.seccomp_filters, None);
+        assert_eq!(security.is_confined(), Some(false));
+        assert_eq!(Security::default().is_confined(), None);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::{
+    process::{
+        unix::{FdKind, ProcRoot, UnixProcess},
+        Pid,
+    },
+    ProcessServerResult,
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+const NET: &str = "net";
+const NET_NAMESPACE: &str = "ns/net";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Tcp6,
+    Udp,
+    Udp6,
+    Unix,
+}
+
+impl Protocol {
+    const ALL: [Protocol; 5] = [
+        Protocol::Tcp,
+        Protocol::Tcp6,
+        Protocol::Udp,
+        Protocol::Udp6,
+        Protocol::Unix,
+    ];
+
+    /// The name of the table in `/proc/net`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Tcp6 => "tcp6",
+            Protocol::Udp => "udp",
+            Protocol::Udp6 => "udp6",
+            Protocol::Unix => "unix",
+        }
+    }
+}
+
+/// A socket of a network namespace, see `/proc/net/{tcp,tcp6,udp,udp6,unix}`
+/// in https://man7.org/linux/man-pages/man5/proc.5.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socket {
+    pub inode:     u64,
+    pub protocol:  Protocol,
+    /// `None` for the unix sockets
+    pub local:     Option<SocketAddr>,
+    pub remote:    Option<SocketAddr>,
+    /// The path of a unix socket, `None` when unnamed, starts with `@` when
+    /// abstract
+    pub path:      Option<PathBuf>,
+    /// The TCP state as named by the kernel (`LISTEN`, `ESTABLISHED`, ...), the
+    /// UDP sockets are `ESTABLISHED` once connected and `CLOSE` otherwise
+    pub state:     &'static str,
+    pub listening: bool,
+}
+
+/// The names of the `st` column, see `include/net/tcp_states.h`
+fn tcp_state(state: u8) -> &'static str {
+    match state {
+        0x01 => "ESTABLISHED",
+        0x02 => "SYN_SENT",
+        0x03 => "SYN_RECV",
+        0x04 => "FIN_WAIT1",
+        0x05 => "FIN_WAIT2",
+        0x06 => "TIME_WAIT",
+        0x07 => "CLOSE",
+        0x08 => "CLOSE_WAIT",
+        0x09 => "LAST_ACK",
+        0x0A => "LISTEN",
+        0x0B => "CLOSING",
+        0x0C => "NEW_SYN_RECV",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Parse an `address:port` of the inet tables, the address is the raw memory
+/// of the kernel (so in host order by 32 bits words) and the port is in big
+/// endian
+fn parse_address(value: &str) -> Option<SocketAddr> {
+    let (address, port) = value.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let words = (0..address.len() / 8)
+        .map(|i| u32::from_str_radix(address.get(i * 8..i * 8 + 8)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    let address = match words[..] {
+        [word] => IpAddr::V4(Ipv4Addr::from(word.to_ne_bytes())),
+        [a, b, c, d] => {
+            let mut bytes = [0; 16];
+            for (chunk, word) in bytes.chunks_mut(4).zip([a, b, c, d]) {
+                chunk.copy_from_slice(&word.to_ne_bytes());
+            }
+            IpAddr::V6(Ipv6Addr::from(bytes))
+        },
+        _ => return None,
+    };
+    Some(SocketAddr::new(address, port))
+}
+
+fn parse_inet_line(protocol: Protocol, line: &str) -> Option<Socket> {
+    // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid
+    // timeout inode
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let local = parse_address(fields.get(1)?)?;
+    let remote = parse_address(fields.get(2)?)?;
+    let state = tcp_state(u8::from_str_radix(fields.get(3)?, 16).ok()?);
+    let listening = match protocol {
+        Protocol::Tcp | Protocol::Tcp6 => state == "LISTEN",
+        // a bound but not connected UDP socket receives from anyone
+        _ => state == "CLOSE" && remote.ip().is_unspecified(),
+    };
+    Some(Socket {
+        inode: fields.get(9)?.parse().ok()?,
+        protocol,
+        local: Some(local),
+        remote: Some(remote),
+        path: None,
+        state,
+        listening,
+    })
+}
+
+fn parse_unix_line(line: &str) -> Option<Socket> {
+    // Num RefCount Protocol Flags Type St Inode Path
+    const ACCEPT_CONNECTIONS: u32 = 1 << 16;
+    // the path is the rest of the line, it can contain spaces
+    let mut fields = Vec::with_capacity(7);
+    let mut rest = line.trim_start();
+    while fields.len() < 7 && !rest.is_empty() {
+        let (field, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+        fields.push(field);
+        rest = tail.trim_start_matches(' ');
+    }
+    let flags = u32::from_str_radix(fields.get(3)?, 16).ok()?;
+    let state = match u8::from_str_radix(fields.get(5)?, 16).ok()? {
+        0x01 => "UNCONNECTED",
+        0x02 => "CONNECTING",
+        0x03 => "CONNECTED",
+        0x04 => "DISCONNECTING",
+        _ => "UNKNOWN",
+    };
+    Some(Socket {
+        inode: fields.get(6)?.parse().ok()?,
+        protocol: Protocol::Unix,
+        local: None,
+        remote: None,
+        path: Some(rest).filter(|x| !x.is_empty()).map(PathBuf::from),
+        state,
+        listening: flags & ACCEPT_CONNECTIONS != 0,
+    })
+}
+
+/// Read all the sockets of the tables of a `net` directory, by inode
+///
+/// Note: a missing table (e.g. without IPv6) is skipped
+pub(crate) fn read_sockets(net: &Path) -> ProcessServerResult<HashMap<u64, Socket>> {
+    let mut sockets = HashMap::new();
+    for protocol in Protocol::ALL {
+        let content = match std::fs::read_to_string(net.join(protocol.name())) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        // the first line is the header
+        for line in content.lines().skip(1) {
+            let socket = match protocol {
+                Protocol::Unix => parse_unix_line(line),
+                _ => parse_inet_line(protocol, line),
+            };
+            match socket {
+                Some(socket) => {
+                    sockets.insert(socket.inode, socket);
+                },
+                None => debug!("Invalid {} socket {line}", protocol.name()),
+            }
+        }
+    }
+    Ok(sockets)
+}
+
+/// Join the socket fds of the processes to the sockets of their network
+/// namespace, the tables of a namespace are read once through its first
+/// process
+///
+/// Note: this needs the fds, the processes whose fds or namespace can't be read
+/// have no sockets, as the processes added by the netlink probe until the next
+/// collection
+pub(crate) fn attach_sockets(root: &ProcRoot, processes: &mut [UnixProcess]) {
+    let mut namespaces = HashMap::<PathBuf, Option<HashMap<u64, Socket>>>::new();
+    for process in processes.iter_mut() {
+        let Some(fds) = process.fds() else {
+            process.sockets = None;
+            continue;
+        };
+        let inodes = fds
+            .iter()
+            .filter_map(|x| match x.kind {
+                FdKind::Socket(inode) => Some(inode),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if inodes.is_empty() {
+            process.sockets = Some(vec![]);
+            continue;
+        }
+        let pid: Pid = process.pid;
+        let process_path = root.process_path(pid);
+        // a reused process keeps the sockets of the previous acquisition otherwise
+        let Ok(namespace) = process_path.join(NET_NAMESPACE).read_link() else {
+            process.sockets = None;
+            continue;
+        };
+        let sockets = namespaces.entry(namespace).or_insert_with(|| {
+            read_sockets(&process_path.join(NET))
+                .map_err(|e| debug!("Could not read the sockets of {pid} {e}"))
+                .ok()
+        });
+        process.sockets = sockets
+            .as_ref()
+            .map(|sockets| inodes.iter().filter_map(|inode| sockets.get(inode).cloned()).collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_parse_lines() {
+        let line = concat!(
+            "   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000",
+            "  1000        0 41234 1 0000000000000000 100 0 0 10 0",
+        );
+        let socket = parse_inet_line(Protocol::Tcp, line).expect("works");
+        assert_eq!(socket.local, Some("127.0.0.1:8080".parse().expect("works")));
+        assert!(socket.listening);
+        assert_eq!(socket.inode, 41234);
+
+        let line = concat!(
+            "   1: 00000000000000000000000001000000:0016 00000000000000000000000000000000:0000",
+            " 0A 00000000:00000000 00:00000000 00000000     0        0 2345 1 0000000000000000",
+            " 100 0 0 10 0",
+        );
+        let socket = parse_inet_line(Protocol::Tcp6, line).expect("works");
+        assert_eq!(socket.local, Some("[::1]:22".parse().expect("works")));
+
+        let line = "0000000000000000: 00000002 00000000 00010000 0001 01 12345 /run/app.sock";
+        let socket = parse_unix_line(line).expect("works");
+        assert!(socket.listening);
+        assert_eq!(socket.path, Some(PathBuf::from("/run/app.sock")));
+
+        let line = "0000000000000000: 00000003 00000000 00000000 0001 03    42 /tmp/my app.sock";
+        let socket = parse_unix_line(line).expect("works");
+        assert_eq!(socket.inode, 42);
+        assert_eq!(socket.path, Some(PathBuf::from("/tmp/my app.sock")));
+
+        let line = "0000000000000000: 00000002 00000000 00000000 0002 01 12346";
+        assert_eq!(parse_unix_line(line).expect("works").path, None);
+    }
+
+    #[test_log::test]
+    fn test_listener() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("works");
+        let port = listener.local_addr().expect("works").port();
+        let root = ProcRoot::default();
+        let pid = std::process::id();
+        let sockets = read_sockets(&root.process_path(pid).join(NET)).expect("works");
+        let fds = crate::process::unix::fds::read_fds(&root, pid).expect("works");
+        let socket = fds
+            .iter()
+            .filter_map(|x| match x.kind {
+                FdKind::Socket(inode) => sockets.get(&inode),
+                _ => None,
+            })
+            .find(|x| x.local.map(|x| x.port()) == Some(port))
+            .expect("works");
+        assert!(socket.listening);
+        assert_eq!(socket.protocol, Protocol::Tcp);
+    }
+}
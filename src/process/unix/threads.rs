@@ -0,0 +1,117 @@
+use crate::{
+    process::{
+        unix::{CpuTimes, ProcRoot},
+        Pid,
+    },
+    ProcessServerError,
+    ProcessServerResult,
+};
+use tracing::debug;
+
+const TASK: &str = "task";
+const STAT: &str = "stat";
+const WCHAN: &str = "wchan";
+const STACK: &str = "stack";
+
+/// A thread of a process, read from `/proc/[pid]/task/[tid]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thread {
+    pub tid:       Pid,
+    /// The comm of the thread, it can differ from the process one (see
+    /// `pthread_setname_np`)
+    pub name:      String,
+    /// The state letter, e.g. `R` running, `S` sleeping or `D` waiting on IO
+    pub state:     char,
+    pub cpu_times: CpuTimes,
+    /// The kernel function the thread is blocked in, `None` when running
+    pub wchan:     Option<String>,
+    /// The kernel stack, one frame per line, `None` without `CAP_SYS_ADMIN`
+    pub stack:     Option<Vec<String>>,
+}
+
+impl Thread {
+    pub(crate) fn read(root: &ProcRoot, pid: Pid, tid: Pid) -> ProcessServerResult<Self> {
+        let thread_path = root.process_path(pid).join(TASK).join(tid.to_string());
+        // See definition in https://man7.org/linux/man-pages/man5/proc.5.html at `/proc/[pid]/stat`
+        let stat = std::fs::read_to_string(thread_path.join(STAT))?;
+        let (start, end) = stat.find('(').zip(stat.rfind(')')).ok_or(
+            ProcessServerError::InvalidUnixStat("Could not find comm".to_string()),
+        )?;
+        let name = stat[start + 1..end].to_string();
+        // fields[0] is state (field 3), fields[11] and fields[12] are utime and stime
+        let fields = stat[end + 1..].split_whitespace().collect::<Vec<_>>();
+        let state = fields.first().and_then(|x| x.chars().next()).ok_or(
+            ProcessServerError::InvalidUnixStat("Missing state field".to_string()),
+        )?;
+        let field = |idx: usize| -> ProcessServerResult<u64> {
+            fields.get(idx).and_then(|x| x.parse().ok()).ok_or(ProcessServerError::InvalidUnixStat(
+                format!("Invalid field {}", idx + 3),
+            ))
+        };
+        let cpu_times = CpuTimes::new(field(11)?, field(12)?);
+        // Note: wchan is "0" when the thread is not blocked, and is hidden (also "0")
+        // without the right to trace the process
+        let wchan = std::fs::read_to_string(thread_path.join(WCHAN))
+            .ok()
+            .filter(|x| !x.is_empty() && x != "0");
+        let stack = match std::fs::read_to_string(thread_path.join(STACK)) {
+            Ok(stack) => Some(stack.lines().map(|x| x.trim().to_string()).collect()),
+            Err(e) => {
+                debug!("Could not read the stack of {tid} {e}");
+                None
+            },
+        };
+        Ok(Self {
+            tid,
+            name,
+            state,
+            cpu_times,
+            wchan,
+            stack,
+        })
+    }
+}
+
+/// Read all the threads of a process, ordered by tid
+///
+/// Note: the threads exiting while being read are skipped
+pub(crate) fn read_threads(root: &ProcRoot, pid: Pid) -> ProcessServerResult<Vec<Thread>> {
+    let mut tids = std::fs::read_dir(root.process_path(pid).join(TASK))?
+        .filter_map(|entry| entry.ok()?.file_name().to_string_lossy().parse::<Pid>().ok())
+        .collect::<Vec<_>>();
+    tids.sort_unstable();
+    Ok(tids
+        .into_iter()
+        .filter_map(|tid| match Thread::read(root, pid, tid) {
+            Ok(thread) => Some(thread),
+            Err(e) => {
+                debug!("Could not read thread {tid} of {pid} {e}");
+                None
+            },
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_log::test]
+    fn test_read_threads() {
+        let pid = std::process::id();
+        let (started, wait) = std::sync::mpsc::channel();
+        let handle = std::thread::Builder::new()
+            .name("threads-test".to_string())
+            .spawn(move || {
+                started.send(()).expect("works");
+                std::thread::park_timeout(std::time::Duration::from_secs(5))
+            })
+            .expect("works");
+        wait.recv().expect("works");
+        let threads = read_threads(&ProcRoot::default(), pid).expect("works");
+        assert_eq!(threads[0].tid, pid);
+        assert!(threads.iter().any(|x| x.name == "threads-test"));
+        handle.thread().unpark();
+        handle.join().expect("works");
+    }
+}
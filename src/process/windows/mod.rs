@@ -5,6 +5,7 @@ use std::{ffi::OsString, path::PathBuf};
 pub struct WindowsProcess {
     pid:        Pid,
     ppid:       Pid,
+    start_time: u64,
     name:       String,
     owner_id:   String,
     owner_name: OsString,
@@ -23,6 +24,10 @@ impl StaticProcess for WindowsProcess {
         self.ppid
     }
 
+    fn start_time(&self) -> u64 {
+        self.start_time
+    }
+
     fn name(&self) -> String {
         self.name.clone()
     }
@@ -66,22 +71,44 @@ pub type WindowsProcessProbe = Box<dyn ProcessProbe<WindowsProcess>>;
 macro_rules! impl_windows_probe {
     ($probe:ty, $method:path) => {
         impl $crate::process::ProcessProbe<$crate::process::WindowsProcess> for $probe {
-            fn collect_processes(&mut self) -> std::io::Result<Vec<&$crate::process::WindowsProcess>> {
-                use $crate::cache::Cache;
-                tracing::debug!("Called collect processes on windows probe");
-                use sysinfo::SystemExt;
-                use sysinfo::PidExt;
-                $crate::process::windows::sysinfo_probe::SYSTEM.write().refresh_system();
-                let read_lock=$crate::process::windows::sysinfo_probe::SYSTEM.read();
-                let pids=read_lock.processes().keys().into_iter().map(|pid| pid.as_u32()).collect::<Vec<u32>>();
-                drop(read_lock);
-                for pid in pids{
-                    match $method(pid) {
-                        Ok(process) => { self.cache.add(process); }
-                        Err(err) => { tracing::debug!("Could not read process for pid {} with error {}",pid,err) }
+            fn collector(&self, _options: $crate::process::AcquisitionOptions) -> $crate::process::ProcessCollector<$crate::process::WindowsProcess> {
+                Box::new(|| {
+                    tracing::debug!("Called collect processes on windows probe");
+                    let started = std::time::Instant::now();
+                    use sysinfo::SystemExt;
+                    use sysinfo::PidExt;
+                    $crate::process::windows::sysinfo_probe::SYSTEM.write().refresh_system();
+                    let read_lock=$crate::process::windows::sysinfo_probe::SYSTEM.read();
+                    let pids=read_lock.processes().keys().into_iter().map(|pid| pid.as_u32()).collect::<Vec<u32>>();
+                    drop(read_lock);
+                    let pids_len = pids.len();
+                    let mut processes = vec![];
+                    for pid in pids{
+                        match $method(pid) {
+                            Ok(process) => { processes.push(process); }
+                            Err(err) => { tracing::debug!("Could not read process for pid {} with error {}",pid,err) }
+                        }
                     }
+                    let timing = $crate::process::CollectionTiming {
+                        threads: 1,
+                        pids: pids_len,
+                        failed: pids_len - processes.len(),
+                        reused: 0,
+                        duration_us: started.elapsed().as_micros() as u64,
+                    };
+                    Ok($crate::process::Collection { processes, timing })
+                })
+            }
+
+            fn publish_processes(&mut self, collection: $crate::process::Collection<$crate::process::WindowsProcess>) -> Vec<&$crate::process::WindowsProcess> {
+                use $crate::cache::Cache;
+                self.cache.clear();
+                for process in collection.processes {
+                    self.cache.add(process);
                 }
-                Ok(self.get_cached_processes())
+                self.cache.inner_mut().set_timing(collection.timing);
+                self.cache.finish_acquisition();
+                self.get_cached_processes()
             }
 
             fn get_cached_processes(&self) -> Vec<&$crate::process::WindowsProcess> {
@@ -89,10 +116,18 @@ macro_rules! impl_windows_probe {
                 self.cache.get()
             }
 
-            fn obtain_channel(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<$crate::process::WindowsProcess>{
+            fn obtain_channel(&mut self) -> tokio::sync::mpsc::UnboundedReceiver<$crate::process::ProcessEvent<$crate::process::WindowsProcess>>{
                 use $crate::cache::ChannelCache;
                 self.cache.subscribe()
             }
+
+            fn process_cache(&self) -> &$crate::process::ChannelProcessCache<$crate::process::WindowsProcess> {
+                &self.cache
+            }
+
+            fn process_cache_mut(&mut self) -> &mut $crate::process::ChannelProcessCache<$crate::process::WindowsProcess> {
+                &mut self.cache
+            }
         }
 
         impl $probe {
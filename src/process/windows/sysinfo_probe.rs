@@ -25,6 +25,8 @@ impl WindowsProcess {
         Ok(Self {
             pid,
             ppid: process.parent().unwrap_or(sysinfo::Pid::from_u32(0)).as_u32(),
+            // sysinfo gives us seconds since the epoch
+            start_time: process.start_time() * 1000,
             name: process.name().to_string(),
             owner_id: process.user_id().map(|x| x.to_string()).unwrap_or(String::new()),
             owner_name: process
@@ -0,0 +1,252 @@
+use crate::manager::Manager;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+        Weak,
+    },
+    time::Duration,
+};
+use tokio::{sync::Notify, time::Instant};
+use tracing::{debug, warn};
+
+/// When the background acquisitions happen
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SchedulerConfig {
+    /// Milliseconds between two periodic acquisitions, `None` disables them
+    pub interval_ms:    Option<u64>,
+    /// A random delay up to this many milliseconds is added to each interval,
+    /// this avoids a fleet of agents acquiring at the same time
+    pub jitter_ms:      u64,
+    /// Minimum milliseconds between two background acquisitions, closer
+    /// triggers are delayed
+    pub min_spacing_ms: u64,
+    /// Acquire when the process receives SIGUSR1 (Unix only)
+    pub on_sigusr1:     bool,
+    /// Acquire when a new subscriber connects to `/data`
+    pub on_subscribe:   bool,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms:    None,
+            jitter_ms:      0,
+            min_spacing_ms: 1000,
+            on_sigusr1:     false,
+            on_subscribe:   false,
+        }
+    }
+}
+
+impl SchedulerConfig {
+    /// Whether anything would ever start an acquisition
+    pub fn is_enabled(&self) -> bool {
+        self.interval_ms.is_some() || self.on_sigusr1 || self.on_subscribe
+    }
+}
+
+/// A background task acquiring the processes, owned by the [`Manager`]
+///
+/// Note: the task is aborted when this is dropped
+pub struct Scheduler {
+    config:  SchedulerConfig,
+    paused:  Arc<AtomicBool>,
+    trigger: Arc<Notify>,
+    handle:  tokio::task::JoinHandle<()>,
+}
+
+impl Scheduler {
+    /// Spawn the background task, it only keeps a weak reference to the manager
+    /// so it stops once the manager is dropped
+    ///
+    /// Note: this must be called from a tokio runtime
+    pub(crate) fn spawn(config: SchedulerConfig, manager: Weak<RwLock<Manager>>) -> Self {
+        let paused = Arc::new(AtomicBool::new(false));
+        let trigger = Arc::new(Notify::new());
+        let handle = tokio::spawn(run(
+            config.clone(),
+            manager,
+            paused.clone(),
+            trigger.clone(),
+        ));
+        Self {
+            config,
+            paused,
+            trigger,
+            handle,
+        }
+    }
+
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    pub fn pause(&self) {
+        debug!("Pausing the scheduler");
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        debug!("Resuming the scheduler");
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Ask for an acquisition as soon as the minimum spacing allows it
+    pub fn trigger(&self) {
+        self.trigger.notify_one();
+    }
+
+    /// A new subscriber connected to `/data`, this triggers an acquisition if
+    /// configured to
+    pub fn subscribed(&self) {
+        if self.config.on_subscribe {
+            self.trigger();
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// A random duration up to `max_ms`, this doesn't need to be cryptographically
+/// secure so we rely on the random keys of the std hasher
+fn jitter(max_ms: u64) -> Duration {
+    if max_ms == 0 {
+        return Duration::ZERO;
+    }
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_millis(random % (max_ms + 1))
+}
+
+#[cfg(unix)]
+async fn sigusr1(signal: &mut Option<tokio::signal::unix::Signal>) {
+    match signal {
+        Some(signal) => {
+            signal.recv().await;
+        },
+        None => std::future::pending().await,
+    }
+}
+
+async fn run(
+    config: SchedulerConfig,
+    manager: Weak<RwLock<Manager>>,
+    paused: Arc<AtomicBool>,
+    trigger: Arc<Notify>,
+) {
+    debug!("Starting the scheduler with {:?}", config);
+    #[cfg(unix)]
+    let mut signal = if config.on_sigusr1 {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::user_defined1()) {
+            Ok(signal) => Some(signal),
+            Err(e) => {
+                warn!("Could not listen to SIGUSR1 {e}");
+                None
+            },
+        }
+    } else {
+        None
+    };
+    let min_spacing = Duration::from_millis(config.min_spacing_ms);
+    let mut last: Option<Instant> = None;
+    loop {
+        let periodic = async {
+            match config.interval_ms {
+                Some(interval) => {
+                    tokio::time::sleep(Duration::from_millis(interval) + jitter(config.jitter_ms))
+                        .await
+                },
+                None => std::future::pending().await,
+            }
+        };
+        #[cfg(unix)]
+        tokio::select! {
+            _ = periodic => debug!("Periodic acquisition"),
+            _ = trigger.notified() => debug!("Triggered acquisition"),
+            _ = sigusr1(&mut signal) => debug!("SIGUSR1 acquisition"),
+        }
+        #[cfg(not(unix))]
+        tokio::select! {
+            _ = periodic => debug!("Periodic acquisition"),
+            _ = trigger.notified() => debug!("Triggered acquisition"),
+        }
+        if paused.load(Ordering::SeqCst) {
+            debug!("Scheduler is paused, skipping acquisition");
+            continue;
+        }
+        if let Some(elapsed) = last.map(|x| x.elapsed()) {
+            if elapsed < min_spacing {
+                tokio::time::sleep(min_spacing - elapsed).await;
+            }
+        }
+        let Some(manager) = manager.upgrade() else {
+            debug!("Manager was dropped, stopping the scheduler");
+            return;
+        };
+        match Manager::acquire(&manager, Default::default()).await {
+            Ok(count) => debug!("Scheduler acquired {} processes", count),
+            Err(e) => warn!("Could not collect processes {:?}", e),
+        }
+        last = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Manager, ManagerConfig, SchedulerConfig};
+    use parking_lot::RwLock;
+    use std::{sync::Arc, time::Duration};
+
+    fn generations(manager: &Arc<RwLock<Manager>>) -> usize {
+        manager.read().process_probe().process_cache().inner().snapshots().count()
+    }
+
+    /// Let the triggered acquisition finish, the clock is paused and does not
+    /// move while it is read
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+
+    #[test_log::test(tokio::test(start_paused = true))]
+    async fn test_periodic_and_pause() {
+        let manager = Arc::new(RwLock::new(Manager::new(ManagerConfig {
+            scheduler: SchedulerConfig {
+                interval_ms: Some(10),
+                min_spacing_ms: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        })));
+        Manager::start_scheduler(&manager);
+        settle().await;
+        assert_eq!(generations(&manager), 0);
+        for expected in 1..=3 {
+            tokio::time::advance(Duration::from_millis(10)).await;
+            settle().await;
+            assert_eq!(generations(&manager), expected);
+        }
+
+        manager.read().scheduler().expect("running").pause();
+        tokio::time::advance(Duration::from_millis(50)).await;
+        settle().await;
+        assert_eq!(generations(&manager), 3);
+
+        manager.read().scheduler().expect("running").resume();
+        tokio::time::advance(Duration::from_millis(10)).await;
+        settle().await;
+        assert_eq!(generations(&manager), 4);
+    }
+}
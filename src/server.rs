@@ -1,14 +1,46 @@
-use crate::{manager::Manager, StaticProcess};
-use std::{ffi::OsString, net::IpAddr, sync::Arc};
+use crate::{
+    manager::Manager,
+    process::map_os_string,
+    AcquisitionOptions,
+    CollectionTiming,
+    DedupPolicy,
+    FieldChange,
+    ProcessEvent,
+    SchedulerConfig,
+    SnapshotRecord,
+    StaticProcess,
+};
+use std::{net::IpAddr, sync::Arc, time::UNIX_EPOCH};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tracing::{debug, warn};
 use warp::Reply;
 
 const MESSAGE: &str = r#"
-POST `/acquire_process_list`
-GET `/processes`
-GET `/search`
+POST `/acquire_process_list?smaps_rollup=<bool>` (unix only, defaults to `--smaps-rollup`)
+GET `/processes?extended=<bool>&sort=<pid|start_time|name|cpu|rss>&order=<asc|desc>`
+GET `/processes/{pid}/threads` (unix only, with `--enumerate-threads`)
+GET `/processes/{pid}/fds` (unix only, with `--fds`)
+GET `/processes/{pid}/libraries` the mapped files (unix only, with `--maps`)
+GET `/files?path=&deleted=<bool>` the processes holding a file open (unix only, with `--fds`)
+GET `/ports?port=&protocol=<tcp|tcp6|udp|udp6|unix>&listening=<bool>` the processes owning the sockets, listening ones by default (unix only, with `--fds`)
+GET `/libraries?path=<path|file name>&deleted=<bool>` the processes mapping a file, e.g. `libssl.so.3` (unix only, with `--maps`)
+GET `/search?pid=&start_time=&username=&extended=<bool>&sort=&order=`
+    unix only: `euid=`, `suid=`, `fsuid=`, `gid=`, `egid=`, `group=<gid>`, `setid=<bool>`,
+        `container=<id prefix|pod uid>`, `unit=<unit|slice>`, `same_namespaces_as=<pid>`,
+        `cap=<effective capability>`, `dangerous_caps=<bool>` (non root with dangerous effective capabilities)
+        `seccomp=<disabled|strict|filter>`, `no_new_privs=<bool>`, `lsm=<label part>`, `confined=<bool>`
+        `exe_status=<present|deleted|memfd|replaced|unreadable>`
+        with `--exe-hash`: `exe_sha256=<hex>[,<hex>...]` (any of the hashes)
+        with `--environ`: `env=NAME`, `env=NAME=VALUE`, `env_flag=<ld_preload|ld_library_path|proxy>`
+GET `/namespaces` the pids sharing the same namespaces, the unreadable ones are skipped (unix only)
 GET `/data`
+GET `/stats`
+GET `/snapshots`
+GET `/snapshots/{id}`
+GET `/diff?from=<gen>&to=<gen>`
+GET `/scheduler`
+POST `/scheduler/pause`
+POST `/scheduler/resume`
 "#;
 
 pub trait Config: serde::de::DeserializeOwned + serde::Serialize + Default {}
@@ -52,17 +84,20 @@ pub struct JSONError {
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct JSONProcess {
-    pid:      u32,
-    name:     String,
-    uid:      String,
+    pid:        u32,
+    // milliseconds since the epoch, (pid, start_time) identify a process
+    start_time: u64,
+    name:       String,
+    uid:        String,
     // Note we convert this to a lossy string because OSString can not be sent
-    username: String,
+    username:   String,
 }
 
 impl JSONProcess {
     pub fn new<T: StaticProcess>(x: &T) -> Self {
         Self {
             pid: x.pid(),
+            start_time: x.start_time(),
             name: x.name(),
             uid: x.owner_id(),
             #[cfg(windows)]
@@ -73,26 +108,1018 @@ impl JSONProcess {
     }
 }
 
+/// A process with the details specific to the platform, returned when
+/// `extended=true`
+#[derive(serde::Serialize)]
+pub struct JSONExtendedProcess {
+    #[serde(flatten)]
+    process:      JSONProcess,
+    #[cfg(unix)]
+    credentials:  JSONCredentials,
+    // `present`, `deleted`, `memfd`, `replaced` or `unreadable`
+    #[cfg(unix)]
+    exe_status:   &'static str,
+    #[cfg(unix)]
+    capabilities: JSONCapabilities,
+    #[cfg(unix)]
+    security:     JSONSecurity,
+    // `None` until the process was seen in two acquisitions
+    #[cfg(unix)]
+    cpu:          Option<JSONCpuUsage>,
+    #[cfg(unix)]
+    memory:       JSONMemory,
+    // `None` unless the fds are read
+    #[cfg(unix)]
+    sockets:      Option<Vec<JSONSocket>>,
+    // `None` unless the environment is read
+    #[cfg(unix)]
+    environ:      Option<JSONEnviron>,
+    #[cfg(unix)]
+    cgroup:       Option<JSONCgroup>,
+    #[cfg(unix)]
+    namespaces:   JSONNamespaces,
+    // the pid in each nested pid namespace, the last one is the pid in the container
+    #[cfg(unix)]
+    nspid:        Vec<u32>,
+    // `None` unless the executables are hashed
+    #[cfg(unix)]
+    exe_sha256:   Option<String>,
+}
+
+impl JSONExtendedProcess {
+    #[cfg(unix)]
+    pub fn new(x: &crate::process::UnixProcess) -> Self {
+        Self {
+            process:      JSONProcess::new(x),
+            credentials:  JSONCredentials::new(x.credentials()),
+            exe_status:   x.exe_status().name(),
+            capabilities: JSONCapabilities::new(x),
+            security:     JSONSecurity::new(x.security()),
+            cpu:          x.cpu_usage().map(|x| JSONCpuUsage {
+                user:   x.user,
+                system: x.system,
+                total:  x.total(),
+            }),
+            memory:      JSONMemory::new(x.memory()),
+            sockets:     process_sockets(x),
+            environ:     x.environ().map(JSONEnviron::new),
+            cgroup:      x.cgroup().map(JSONCgroup::new),
+            namespaces:  JSONNamespaces::new(x.namespaces()),
+            nspid:       x.nspid().to_vec(),
+            exe_sha256:  x.exe_sha256().map(|x| hex(&x)),
+        }
+    }
+
+    #[cfg(windows)]
+    pub fn new(x: &crate::process::WindowsProcess) -> Self {
+        Self {
+            process: JSONProcess::new(x),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONId {
+    id:   u32,
+    // `None` when no user (resp. group) has this id
+    name: Option<String>,
+}
+
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONIdSet {
+    real:      JSONId,
+    effective: JSONId,
+    saved:     JSONId,
+    fs:        JSONId,
+}
+
+#[cfg(unix)]
+impl JSONIdSet {
+    fn new(ids: crate::process::IdSet, name: impl Fn(u32) -> Option<String>) -> Self {
+        let id = |id| JSONId { id, name: name(id) };
+        Self {
+            real:      id(ids.real),
+            effective: id(ids.effective),
+            saved:     id(ids.saved),
+            fs:        id(ids.fs),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONCredentials {
+    uid:    JSONIdSet,
+    gid:    JSONIdSet,
+    groups: Vec<JSONId>,
+}
+
+#[cfg(unix)]
+impl JSONCredentials {
+    pub fn new(x: &crate::process::Credentials) -> Self {
+        let user = |uid| x.user_name(uid).map(|x| map_os_string(&x.to_owned()));
+        let group = |gid| x.group_name(gid).map(|x| map_os_string(&x.to_owned()));
+        Self {
+            uid:    JSONIdSet::new(x.uids(), user),
+            gid:    JSONIdSet::new(x.gids(), group),
+            groups: x
+                .groups()
+                .iter()
+                .map(|&id| JSONId {
+                    id,
+                    name: group(id),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The CPU used since the previous acquisition, in percent of all the cores
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONCpuUsage {
+    user:   f64,
+    system: f64,
+    total:  f64,
+}
+
+/// The memory of a process in bytes
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONMemory {
+    vsz:    u64,
+    rss:    u64,
+    shared: u64,
+    swap:   u64,
+    // `None` unless the proportional memory is read, see `--smaps-rollup`
+    pss:    Option<u64>,
+    uss:    Option<u64>,
+}
+
+#[cfg(unix)]
+impl JSONMemory {
+    fn new(x: crate::process::Memory) -> Self {
+        Self {
+            vsz:    x.vsz,
+            rss:    x.rss,
+            shared: x.shared,
+            swap:   x.swap,
+            pss:    x.proportional.map(|x| x.pss),
+            uss:    x.proportional.map(|x| x.uss),
+        }
+    }
+}
+
+/// A thread of a process, see `/processes/{pid}/threads`
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONThread {
+    tid:          u32,
+    name:         String,
+    state:        char,
+    user_ticks:   u64,
+    system_ticks: u64,
+    wchan:        Option<String>,
+    // `None` when the stack can not be read (it needs `CAP_SYS_ADMIN`)
+    stack:        Option<Vec<String>>,
+}
+
+#[cfg(unix)]
+impl JSONThread {
+    pub fn new(x: &crate::process::Thread) -> Self {
+        Self {
+            tid:          x.tid,
+            name:         x.name.clone(),
+            state:        x.state,
+            user_ticks:   x.cpu_times.user_ticks,
+            system_ticks: x.cpu_times.system_ticks,
+            wchan:        x.wchan.clone(),
+            stack:        x.stack.clone(),
+        }
+    }
+}
+
+/// An open file descriptor, see `/processes/{pid}/fds`
+#[derive(serde::Serialize)]
+pub struct JSONFd {
+    fd:      u32,
+    // `file`, `socket`, `pipe`, `anon_inode` or `other`
+    kind:    &'static str,
+    // the link as read, e.g. `socket:[1234]` or `/tmp/x (deleted)`
+    target:  String,
+    // the file without the ` (deleted)` suffix, only for the files
+    path:    Option<String>,
+    // the inode of the sockets and pipes
+    inode:   Option<u64>,
+    deleted: bool,
+    // the open flags in octal as in `/proc/[pid]/fdinfo`
+    flags:   Option<String>,
+    pos:     Option<u64>,
+}
+
+#[cfg(unix)]
+impl JSONFd {
+    pub fn new(x: &crate::process::Fd) -> Self {
+        use crate::process::FdKind;
+        let (kind, inode) = match x.kind {
+            FdKind::File => ("file", None),
+            FdKind::Socket(inode) => ("socket", Some(inode)),
+            FdKind::Pipe(inode) => ("pipe", Some(inode)),
+            FdKind::AnonInode => ("anon_inode", None),
+            FdKind::Other => ("other", None),
+        };
+        Self {
+            fd: x.fd,
+            kind,
+            target: map_os_string(&x.target.clone().into_os_string()),
+            path: (x.kind == FdKind::File).then(|| map_os_string(&x.path().as_os_str().to_owned())),
+            inode,
+            deleted: x.deleted,
+            flags: x.flags.map(|x| format!("{x:07o}")),
+            pos: x.pos,
+        }
+    }
+}
+
+/// The environment of a process with the variables worth a look
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONEnviron {
+    // `NAME=VALUE` as in `/proc/[pid]/environ`
+    variables:       Vec<String>,
+    ld_preload:      bool,
+    ld_library_path: bool,
+    proxy:           bool,
+}
+
+#[cfg(unix)]
+impl JSONEnviron {
+    pub fn new(x: &crate::process::Environ) -> Self {
+        Self {
+            variables:       x
+                .variables()
+                .iter()
+                .map(|(name, value)| {
+                    let mut variable = name.clone();
+                    variable.push("=");
+                    variable.push(value);
+                    map_os_string(&variable)
+                })
+                .collect(),
+            ld_preload:      x.has_ld_preload(),
+            ld_library_path: x.has_ld_library_path(),
+            proxy:           x.has_proxy(),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONCgroupEntry {
+    hierarchy:   u32,
+    controllers: Vec<String>,
+    path:        String,
+}
+
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONContainer {
+    runtime: &'static str,
+    id:      String,
+    pod_uid: Option<String>,
+    qos:     Option<&'static str>,
+}
+
+/// The cgroups of a process and what they tell about it
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONCgroup {
+    entries:   Vec<JSONCgroupEntry>,
+    // `None` outside of a container
+    container: Option<JSONContainer>,
+    unit:      Option<String>,
+    slice:     Option<String>,
+}
+
+#[cfg(unix)]
+impl JSONCgroup {
+    pub fn new(x: &crate::process::Cgroup) -> Self {
+        Self {
+            entries:   x
+                .entries
+                .iter()
+                .map(|x| JSONCgroupEntry {
+                    hierarchy:   x.hierarchy,
+                    controllers: x.controllers.clone(),
+                    path:        x.path.clone(),
+                })
+                .collect(),
+            container: x.container.as_ref().map(|x| JSONContainer {
+                runtime: x.runtime.name(),
+                id:      x.id.clone(),
+                pod_uid: x.pod_uid.clone(),
+                qos:     x.qos,
+            }),
+            unit:      x.unit.clone(),
+            slice:     x.slice.clone(),
+        }
+    }
+}
+
+/// The inodes of the namespaces of a process, `None` when they can't be read
+#[derive(serde::Serialize, Clone, PartialEq, Eq, Hash)]
+pub struct JSONNamespaces {
+    pid:    Option<u64>,
+    mnt:    Option<u64>,
+    net:    Option<u64>,
+    user:   Option<u64>,
+    uts:    Option<u64>,
+    ipc:    Option<u64>,
+    cgroup: Option<u64>,
+}
+
+#[cfg(unix)]
+impl JSONNamespaces {
+    pub fn new(x: crate::process::Namespaces) -> Self {
+        Self {
+            pid:    x.pid,
+            mnt:    x.mnt,
+            net:    x.net,
+            user:   x.user,
+            uts:    x.uts,
+            ipc:    x.ipc,
+            cgroup: x.cgroup,
+        }
+    }
+}
+
+/// The processes sharing the same namespaces, see `/namespaces`
+#[derive(serde::Serialize)]
+pub struct JSONNamespaceGroup {
+    namespaces: JSONNamespaces,
+    pids:       Vec<u32>,
+}
+
+/// A socket of a process, see `/ports`
+#[derive(serde::Serialize)]
+pub struct JSONSocket {
+    inode:     u64,
+    protocol:  &'static str,
+    local:     Option<String>,
+    remote:    Option<String>,
+    // the path of the named unix sockets
+    path:      Option<String>,
+    state:     &'static str,
+    listening: bool,
+}
+
+#[cfg(unix)]
+impl JSONSocket {
+    pub fn new(x: &crate::process::Socket) -> Self {
+        Self {
+            inode:     x.inode,
+            protocol:  x.protocol.name(),
+            local:     x.local.map(|x| x.to_string()),
+            remote:    x.remote.map(|x| x.to_string()),
+            path:      x.path.as_ref().map(|x| map_os_string(&x.clone().into_os_string())),
+            state:     x.state,
+            listening: x.listening,
+        }
+    }
+}
+
+impl JSONSocket {
+    fn port(&self) -> Option<u16> {
+        self.local.as_ref()?.parse::<std::net::SocketAddr>().ok().map(|x| x.port())
+    }
+}
+
+/// A process owning a socket, see `/ports`
+#[derive(serde::Serialize)]
+pub struct JSONPort {
+    process: JSONProcess,
+    socket:  JSONSocket,
+}
+
+/// A file mapped by a process, see `/processes/{pid}/libraries`
+#[derive(serde::Serialize)]
+pub struct JSONMapping {
+    // `file`, `memfd` or `anonymous`
+    kind:       &'static str,
+    // `None` when anonymous
+    path:       Option<String>,
+    inode:      u64,
+    deleted:    bool,
+    executable: bool,
+    library:    bool,
+    start:      u64,
+    size:       u64,
+}
+
+#[cfg(unix)]
+impl JSONMapping {
+    pub fn new(x: &crate::process::Mapping) -> Self {
+        Self {
+            kind:       x.kind.name(),
+            path:       x.path.as_ref().map(|x| map_os_string(&x.clone().into_os_string())),
+            inode:      x.inode,
+            deleted:    x.deleted,
+            executable: x.executable,
+            library:    x.is_library(),
+            start:      x.start,
+            size:       x.size,
+        }
+    }
+}
+
+/// A process mapping a file, see `/libraries`
+#[derive(serde::Serialize)]
+pub struct JSONMappedFile {
+    process: JSONProcess,
+    mapping: JSONMapping,
+}
+
+/// A process holding a file open, see `/files`
+#[derive(serde::Serialize)]
+pub struct JSONOpenFile {
+    process: JSONProcess,
+    fd:      JSONFd,
+}
+
+/// The capability sets of a process by name
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONCapabilities {
+    inheritable: Vec<String>,
+    permitted:   Vec<String>,
+    effective:   Vec<String>,
+    bounding:    Vec<String>,
+    ambient:     Vec<String>,
+    // the dangerous effective capabilities of a non root process, empty for root
+    dangerous:   Vec<&'static str>,
+}
+
+#[cfg(unix)]
+impl JSONCapabilities {
+    pub fn new(x: &crate::process::UnixProcess) -> Self {
+        let capabilities = x.capabilities();
+        Self {
+            inheritable: capabilities.inheritable.names(),
+            permitted:   capabilities.permitted.names(),
+            effective:   capabilities.effective.names(),
+            bounding:    capabilities.bounding.names(),
+            ambient:     capabilities.ambient.names(),
+            dangerous:   if x.has_dangerous_capabilities() {
+                capabilities.effective.dangerous()
+            } else {
+                vec![]
+            },
+        }
+    }
+}
+
+/// How a process is confined, `None` when unknown
+#[cfg(unix)]
+#[derive(serde::Serialize)]
+pub struct JSONSecurity {
+    seccomp:                  Option<&'static str>,
+    seccomp_filters:          Option<u32>,
+    no_new_privs:             Option<bool>,
+    speculation_store_bypass: Option<String>,
+    lsm_label:                Option<String>,
+    confined:                 Option<bool>,
+}
+
+#[cfg(unix)]
+impl JSONSecurity {
+    pub fn new(x: &crate::process::Security) -> Self {
+        Self {
+            seccomp:                  x.seccomp.map(|x| x.name()),
+            seccomp_filters:          x.seccomp_filters,
+            no_new_privs:             x.no_new_privs,
+            speculation_store_bypass: x.speculation_store_bypass.clone(),
+            lsm_label:                x.lsm_label.clone(),
+            confined:                 x.is_confined(),
+        }
+    }
+}
+
+/// A process along the fields that changed, sent as a `change` event on `/data`
+#[derive(serde::Serialize)]
+pub struct JSONProcessChange {
+    #[serde(flatten)]
+    process: JSONProcess,
+    changes: Vec<FieldChange>,
+}
+
+impl JSONProcessChange {
+    pub fn new<T: StaticProcess>(x: &T, changes: Vec<FieldChange>) -> Self {
+        Self {
+            process: JSONProcess::new(x),
+            changes,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct JSONSnapshotSummary {
+    id:        u64,
+    // milliseconds since the epoch
+    timestamp: u64,
+    processes: usize,
+    timing:    Option<CollectionTiming>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct JSONStats {
+    // the number of processes in the last acquisition
+    processes: usize,
+    // the number of identities remembered to deduplicate `/data`
+    seen:      usize,
+    dedup:     DedupPolicy,
+    // how long reading the last acquisition took
+    timing:    Option<CollectionTiming>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct JSONScheduler {
+    running: bool,
+    paused:  bool,
+    config:  Option<SchedulerConfig>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct DiffParams {
+    from: u64,
+    to:   u64,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct ListParams {
+    extended: Option<bool>,
+    sort:     Option<SortKey>,
+    order:    Option<SortOrder>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum SortKey {
+    Pid,
+    StartTime,
+    Name,
+    // the processes without a usage yet come first (resp. last)
+    Cpu,
+    Rss,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct FilesParams {
+    path:    Option<String>,
+    deleted: Option<bool>,
+}
+
+impl FilesParams {
+    fn matches(&self, fd: &JSONFd) -> bool {
+        fd.path.is_some()
+            && self.path.as_ref().map(|x| fd.path.as_ref() == Some(x)).unwrap_or(true)
+            && self.deleted.map(|x| x == fd.deleted).unwrap_or(true)
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct LibrariesParams {
+    // the full path or the file name
+    path:    Option<String>,
+    deleted: Option<bool>,
+}
+
+impl LibrariesParams {
+    fn matches(&self, mapping: &JSONMapping) -> bool {
+        let Some(path) = mapping.path.as_ref() else {
+            return false;
+        };
+        self.path
+            .as_ref()
+            .map(|x| path == x || path.rsplit('/').next() == Some(x.as_str()))
+            .unwrap_or(true)
+            && self.deleted.map(|x| x == mapping.deleted).unwrap_or(true)
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct PortsParams {
+    port:      Option<u16>,
+    protocol:  Option<String>,
+    // `true` when not set
+    listening: Option<bool>,
+}
+
+impl PortsParams {
+    fn matches(&self, socket: &JSONSocket) -> bool {
+        self.port.map(|x| socket.port() == Some(x)).unwrap_or(true)
+            && self.protocol.as_ref().map(|x| socket.protocol == x).unwrap_or(true)
+            && self.listening.unwrap_or(true) == socket.listening
+    }
+}
+
 #[derive(serde::Deserialize, serde::Serialize)]
 struct SearchParams {
-    pid:      Option<u32>,
-    username: Option<String>,
+    pid:                Option<u32>,
+    start_time:         Option<u64>,
+    username:           Option<String>,
+    extended:           Option<bool>,
+    sort:               Option<SortKey>,
+    order:              Option<SortOrder>,
+    #[cfg(unix)]
+    euid:               Option<u32>,
+    #[cfg(unix)]
+    suid:               Option<u32>,
+    #[cfg(unix)]
+    fsuid:              Option<u32>,
+    #[cfg(unix)]
+    gid:                Option<u32>,
+    #[cfg(unix)]
+    egid:               Option<u32>,
+    // a supplementary group
+    #[cfg(unix)]
+    group:              Option<u32>,
+    // the processes running with other ids than their real ones (setuid, setgid)
+    #[cfg(unix)]
+    setid:              Option<bool>,
+    // `NAME` or `NAME=VALUE`
+    #[cfg(unix)]
+    env:                Option<String>,
+    #[cfg(unix)]
+    env_flag:           Option<String>,
+    // a prefix of the container id (e.g. the 12 digits of docker) or the pod uid
+    #[cfg(unix)]
+    container:          Option<String>,
+    // a systemd unit or slice
+    #[cfg(unix)]
+    unit:               Option<String>,
+    // the processes sharing all the namespaces of this pid that could be read
+    #[cfg(unix)]
+    same_namespaces_as: Option<u32>,
+    // an effective capability, e.g. `CAP_SYS_ADMIN` or `sys_admin`
+    #[cfg(unix)]
+    cap:                Option<String>,
+    #[cfg(unix)]
+    dangerous_caps:     Option<bool>,
+    // the unknown values never match these
+    #[cfg(unix)]
+    seccomp:            Option<String>,
+    #[cfg(unix)]
+    no_new_privs:       Option<bool>,
+    #[cfg(unix)]
+    lsm:                Option<String>,
+    // seccomp filtered or under an LSM profile, see `Security::is_confined`
+    #[cfg(unix)]
+    confined:           Option<bool>,
+    #[cfg(unix)]
+    exe_status:         Option<String>,
+    // comma separated, the processes whose executable is not hashed never match
+    #[cfg(unix)]
+    exe_sha256:         Option<String>,
+    #[cfg(unix)]
+    #[serde(skip)]
+    namespaces:         Option<crate::process::Namespaces>,
 }
 
+impl SearchParams {
+    /// Find the namespaces of `same_namespaces_as`, they are unknown when the
+    /// process is not in the processes
+    #[cfg(unix)]
+    fn resolve(&mut self, processes: &[&Process]) {
+        self.namespaces = self.same_namespaces_as.map(|pid| {
+            processes.iter().find(|x| x.pid() == pid).map(|x| x.namespaces()).unwrap_or_default()
+        });
+    }
 
-fn map_os_string(s: &OsString) -> String {
-    match s.to_str() {
-        None => {
-            #[cfg(windows)]
-            let buffer = std::os::windows::ffi::OsStrExt::encode_wide(s.as_os_str())
-                .flat_map(|x| [(x >> 8) as u8, x as u8])
-                .collect::<Vec<_>>();
-            #[cfg(unix)]
-            let buffer = std::os::unix::ffi::OsStrExt::as_bytes(s.as_os_str());
-            base64::Engine::encode(&base64::prelude::BASE64_URL_SAFE, buffer)
-        },
-        Some(s) => s.to_string(),
+    #[cfg(windows)]
+    fn resolve(&mut self, _processes: &[&Process]) {
+    }
+
+    #[cfg(unix)]
+    fn matches_extended(&self, p: &crate::process::UnixProcess) -> bool {
+        let credentials = p.credentials();
+        let (uids, gids) = (credentials.uids(), credentials.gids());
+        let is = |param: Option<u32>, value: u32| param.map(|x| x == value).unwrap_or(true);
+        is(self.euid, uids.effective)
+            && is(self.suid, uids.saved)
+            && is(self.fsuid, uids.fs)
+            && is(self.gid, gids.real)
+            && is(self.egid, gids.effective)
+            && self.group.map(|x| credentials.groups().contains(&x)).unwrap_or(true)
+            && self.setid.map(|x| x == (uids.is_changed() || gids.is_changed())).unwrap_or(true)
+            && self.matches_environ(p.environ())
+            && self.matches_cgroup(p.cgroup())
+            && self.namespaces.map(|x| same_namespaces(&x, &p.namespaces())).unwrap_or(true)
+            && self.cap.as_ref().map(|x| p.capabilities().effective.contains(x)).unwrap_or(true)
+            && self.dangerous_caps.map(|x| x == p.has_dangerous_capabilities()).unwrap_or(true)
+            && self.matches_security(p.security())
+            && self.exe_status.as_ref().map(|x| p.exe_status().name() == x).unwrap_or(true)
+            && self.matches_exe_sha256(p.exe_sha256())
+    }
+
+    #[cfg(unix)]
+    fn matches_exe_sha256(&self, exe_sha256: Option<[u8; 32]>) -> bool {
+        let Some(hashes) = self.exe_sha256.as_ref() else {
+            return true;
+        };
+        let Some(exe_sha256) = exe_sha256.map(|x| hex(&x)) else {
+            return false;
+        };
+        hashes.split(',').any(|x| x.trim().eq_ignore_ascii_case(&exe_sha256))
+    }
+
+    #[cfg(unix)]
+    fn matches_security(&self, security: &crate::process::Security) -> bool {
+        self.seccomp
+            .as_ref()
+            .map(|x| security.seccomp.map(|s| s.name() == x).unwrap_or(false))
+            .unwrap_or(true)
+            && self.no_new_privs.map(|x| security.no_new_privs == Some(x)).unwrap_or(true)
+            && self
+                .lsm
+                .as_ref()
+                .map(|x| {
+                    security.lsm_label.as_ref().map(|l| l.contains(x.as_str())).unwrap_or(false)
+                })
+                .unwrap_or(true)
+            && self.confined.map(|x| security.is_confined() == Some(x)).unwrap_or(true)
+    }
+
+    #[cfg(unix)]
+    fn matches_cgroup(&self, cgroup: Option<&crate::process::Cgroup>) -> bool {
+        let container = cgroup.and_then(|x| x.container.as_ref());
+        self.container
+            .as_ref()
+            .map(|x| {
+                container
+                    .map(|c| c.id.starts_with(x.as_str()) || c.pod_uid.as_ref() == Some(x))
+                    .unwrap_or(false)
+            })
+            .unwrap_or(true)
+            && self
+                .unit
+                .as_ref()
+                .map(|x| {
+                    cgroup
+                        .map(|c| c.unit.as_ref() == Some(x) || c.slice.as_ref() == Some(x))
+                        .unwrap_or(false)
+                })
+                .unwrap_or(true)
     }
+
+    /// Note: the processes whose environment is not read never match an
+    /// environment filter
+    #[cfg(unix)]
+    fn matches_environ(&self, environ: Option<&crate::process::Environ>) -> bool {
+        use std::os::unix::ffi::OsStrExt;
+        if self.env.is_none() && self.env_flag.is_none() {
+            return true;
+        }
+        let Some(environ) = environ else {
+            return false;
+        };
+        let env = self.env.as_ref().map(|env| match env.split_once('=') {
+            Some((name, value)) => environ.get(name).map(|x| x.as_bytes() == value.as_bytes()),
+            None => environ.get(env).map(|_| true),
+        });
+        let flag = self.env_flag.as_ref().map(|flag| match flag.as_str() {
+            "ld_preload" => environ.has_ld_preload(),
+            "ld_library_path" => environ.has_ld_library_path(),
+            "proxy" => environ.has_proxy(),
+            _ => false,
+        });
+        env.map(|x| x.unwrap_or(false)).unwrap_or(true) && flag.unwrap_or(true)
+    }
+
+    #[cfg(windows)]
+    fn matches_extended(&self, _p: &crate::process::WindowsProcess) -> bool {
+        true
+    }
+}
+
+#[cfg(unix)]
+type Process = crate::process::UnixProcess;
+#[cfg(windows)]
+type Process = crate::process::WindowsProcess;
+
+/// Whether a process is in all the known namespaces of another, nothing is in
+/// unknown namespaces
+#[cfg(unix)]
+fn same_namespaces(reference: &crate::process::Namespaces, x: &crate::process::Namespaces) -> bool {
+    let mut known = false;
+    for ((_, reference), (_, value)) in reference.iter().zip(x.iter()) {
+        if let Some(reference) = reference {
+            known = true;
+            if value != Some(reference) {
+                return false;
+            }
+        }
+    }
+    known
+}
+
+#[cfg(unix)]
+fn cpu_total(p: &Process) -> Option<f64> {
+    p.cpu_usage().map(|x| x.total())
+}
+
+#[cfg(windows)]
+fn cpu_total(_p: &Process) -> Option<f64> {
+    None
+}
+
+#[cfg(unix)]
+fn rss(p: &Process) -> u64 {
+    p.memory().rss
+}
+
+#[cfg(windows)]
+fn rss(_p: &Process) -> u64 {
+    0
+}
+
+/// The threads of a process, `None` when they are not enumerated
+#[cfg(unix)]
+fn process_threads(p: &Process) -> Option<Vec<JSONThread>> {
+    p.threads().map(|x| x.iter().map(JSONThread::new).collect())
+}
+
+#[cfg(windows)]
+fn process_threads(_p: &Process) -> Option<Vec<()>> {
+    None
+}
+
+/// The open file descriptors of a process, `None` when they are not read
+#[cfg(unix)]
+fn process_fds(p: &Process) -> Option<Vec<JSONFd>> {
+    p.fds().map(|x| x.iter().map(JSONFd::new).collect())
+}
+
+#[cfg(windows)]
+fn process_fds(_p: &Process) -> Option<Vec<JSONFd>> {
+    None
+}
+
+/// The mapped files of a process, `None` when the maps are not read
+#[cfg(unix)]
+fn process_mappings(p: &Process) -> Option<Vec<JSONMapping>> {
+    p.mappings().map(|x| x.iter().map(JSONMapping::new).collect())
+}
+
+#[cfg(windows)]
+fn process_mappings(_p: &Process) -> Option<Vec<JSONMapping>> {
+    None
+}
+
+/// The sockets of a process, `None` when the fds are not read
+#[cfg(unix)]
+fn process_sockets(p: &Process) -> Option<Vec<JSONSocket>> {
+    p.sockets().map(|x| x.iter().map(JSONSocket::new).collect())
+}
+
+#[cfg(windows)]
+fn process_sockets(_p: &Process) -> Option<Vec<JSONSocket>> {
+    None
+}
+
+#[cfg(unix)]
+fn process_namespaces(p: &Process) -> Option<JSONNamespaces> {
+    let namespaces = p.namespaces();
+    (!namespaces.is_unknown()).then(|| JSONNamespaces::new(namespaces))
+}
+
+#[cfg(windows)]
+fn process_namespaces(_p: &Process) -> Option<JSONNamespaces> {
+    None
+}
+
+/// Reply with a detail of a process of the last acquisition, it is a conflict
+/// when the detail is not read
+fn reply_detail<T: serde::Serialize>(
+    manager: &Manager,
+    pid: u32,
+    what: &str,
+    detail: fn(&Process) -> Option<T>,
+) -> warp::reply::Response {
+    let processes = manager.process_probe().get_cached_processes();
+    let Some(process) = processes.iter().find(|x| x.pid() == pid) else {
+        return warp::reply::with_status(
+            warp::reply::json(&JSONError {
+                error: format!("No process {pid} in the last acquisition"),
+            }),
+            warp::http::StatusCode::NOT_FOUND,
+        )
+        .into_response();
+    };
+    match detail(process) {
+        Some(detail) => warp::reply::json(&detail).into_response(),
+        None => warp::reply::with_status(
+            warp::reply::json(&JSONError {
+                error: format!("The {what} of {pid} are not read"),
+            }),
+            warp::http::StatusCode::CONFLICT,
+        )
+        .into_response(),
+    }
+}
+
+/// Reply with the details of the processes of the last acquisition that match
+/// a lookup along their process, it is a conflict when no process has the
+/// detail read
+///
+/// Note: a lookup without a filter (no `matches`) is refused, it would reply
+/// with a detail of every process
+fn reply_lookup<T, R: serde::Serialize>(
+    manager: &Manager,
+    what: &str,
+    detail: fn(&Process) -> Option<Vec<T>>,
+    matches: Option<impl Fn(&T) -> bool>,
+    entry: fn(JSONProcess, T) -> R,
+) -> warp::reply::Response {
+    let Some(matches) = matches else {
+        return warp::reply::with_status(
+            warp::reply::json(&JSONError {
+                error: "Expected a filter".to_string(),
+            }),
+            warp::http::StatusCode::BAD_REQUEST,
+        )
+        .into_response();
+    };
+    let processes = manager.process_probe().get_cached_processes();
+    let mut read = false;
+    let mut entries = vec![];
+    for process in processes {
+        let Some(details) = detail(process) else {
+            continue;
+        };
+        read = true;
+        entries.extend(
+            details.into_iter().filter(|x| matches(x)).map(|x| entry(JSONProcess::new(process), x)),
+        );
+    }
+    if !read {
+        return warp::reply::with_status(
+            warp::reply::json(&JSONError {
+                error: format!("The {what} are not read"),
+            }),
+            warp::http::StatusCode::CONFLICT,
+        )
+        .into_response();
+    }
+    warp::reply::json(&entries).into_response()
+}
+
+/// Sort the processes in place, they are left in pid order without a key
+fn sort_processes(processes: &mut [&Process], sort: Option<SortKey>, order: Option<SortOrder>) {
+    let Some(sort) = sort else {
+        return;
+    };
+    // Note: the sort is stable so the processes with the same key stay in pid order
+    processes.sort_by(|a, b| {
+        let ordering = match sort {
+            SortKey::Pid => a.pid().cmp(&b.pid()),
+            SortKey::StartTime => a.start_time().cmp(&b.start_time()),
+            SortKey::Name => a.name().cmp(&b.name()),
+            SortKey::Cpu => cpu_total(a).unwrap_or(-1.0).total_cmp(&cpu_total(b).unwrap_or(-1.0)),
+            SortKey::Rss => rss(a).cmp(&rss(b)),
+        };
+        match order.unwrap_or_default() {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+}
+
+/// Reply with the extended representation of the processes if asked
+macro_rules! reply_processes {
+    ($processes:expr, $extended:expr) => {
+        if $extended.unwrap_or(false) {
+            warp::reply::json(&$processes.map(|&x| JSONExtendedProcess::new(x)).collect::<Vec<_>>())
+        } else {
+            warp::reply::json(&$processes.map(|&x| JSONProcess::new(x)).collect::<Vec<_>>())
+        }
+    };
+}
+
+
+/// Lowercase hexadecimal, as `sha256sum` prints
+#[cfg(unix)]
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
 }
 
 
@@ -108,47 +1135,53 @@ impl Server for WarpServer {
         use futures_util::StreamExt;
         use warp::Filter;
         let manager1 = manager.clone();
-        let acquire_route = warp::path("acquire_process_list").and(warp::post()).map(move || {
-            debug!("Called acquire_process_list");
-            let mut manager = manager1.write();
-            match manager.process_probe_mut().collect_processes() {
-                Ok(_) => warp::reply::Response::default(),
-                Err(e) => {
-                    warn!("Could not collect processes {:?}", e);
-                    warp::reply::with_status(
-                        warp::reply::json(&JSONError {
-                            error: format!("Could not collect processes {:?}", e),
-                        }),
-                        warp::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    )
-                    .into_response()
-                },
-            }
-        });
+        let acquire_route = warp::path("acquire_process_list")
+            .and(warp::post())
+            .and(warp::query::<AcquisitionOptions>())
+            .and_then(move |options: AcquisitionOptions| {
+                let manager = manager1.clone();
+                async move {
+                    debug!("Called acquire_process_list with {:?}", options);
+                    let response = match Manager::acquire(&manager, options).await {
+                        Ok(_) => warp::reply::Response::default(),
+                        Err(e) => {
+                            warn!("Could not collect processes {:?}", e);
+                            warp::reply::with_status(
+                                warp::reply::json(&JSONError {
+                                    error: format!("Could not collect processes {:?}", e),
+                                }),
+                                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                            )
+                            .into_response()
+                        },
+                    };
+                    Ok::<_, warp::Rejection>(response)
+                }
+            });
         let manager2 = manager.clone();
-        let processes_route = warp::path("processes").and(warp::get()).map(move || {
-            debug!("Called processes");
-            let manager = manager2.read();
-            let processes = manager
-                .process_probe()
-                .get_cached_processes()
-                .iter()
-                .map(|&x| JSONProcess::new(x))
-                .collect::<Vec<_>>();
-            warp::reply::json(&processes)
-        });
+        let processes_route = warp::path("processes")
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(warp::query::<ListParams>())
+            .map(move |params: ListParams| {
+                debug!("Called processes");
+                let manager = manager2.read();
+                let mut processes = manager.process_probe().get_cached_processes();
+                sort_processes(&mut processes, params.sort, params.order);
+                reply_processes!(processes.iter(), params.extended)
+            });
 
         let manager3 = manager.clone();
         let search_route = warp::path("search")
             .and(warp::get())
             .and(warp::query::<SearchParams>())
-            .map(move |params: SearchParams| {
+            .map(move |mut params: SearchParams| {
                 debug!("Called search");
                 let manager = manager3.read();
-                let processes = manager
-                    .process_probe()
-                    .get_cached_processes()
-                    .iter()
+                let processes = manager.process_probe().get_cached_processes();
+                params.resolve(&processes);
+                let mut processes = processes
+                    .into_iter()
                     .filter(|&p| {
                         #[cfg(windows)]
                         let username = map_os_string(&p.owner_name());
@@ -156,26 +1189,276 @@ impl Server for WarpServer {
                         let username = map_os_string(&p.owner_name());
                         params.username.as_ref().map(|x| username.eq(x)).unwrap_or(true)
                             && params.pid.as_ref().map(|x| p.pid().eq(x)).unwrap_or(true)
+                            && params
+                                .start_time
+                                .as_ref()
+                                .map(|x| p.start_time().eq(x))
+                                .unwrap_or(true)
+                            && params.matches_extended(p)
                     })
-                    .map(|&x| JSONProcess::new(x))
                     .collect::<Vec<_>>();
-                warp::reply::json(&processes)
+                sort_processes(&mut processes, params.sort, params.order);
+                reply_processes!(processes.iter(), params.extended)
             });
         let manager4 = manager.clone();
         let data_route = warp::path("data").and(warp::get()).map(move || {
             debug!("Called data");
             let mut manager = manager4.write();
             let rx = manager.process_probe_mut().obtain_channel();
+            if let Some(scheduler) = manager.scheduler() {
+                scheduler.subscribed();
+            }
             drop(manager);
             let rx = UnboundedReceiverStream::new(rx);
-            let stream = rx.map(|p| warp::sse::Event::default().json_data(JSONProcess::new(&p)));
+            let stream = rx.map(|event| {
+                // Note: the data of `new` and `exit` events is the same as before events were
+                // named, so consumers of new processes only have to filter on the name
+                let sse = warp::sse::Event::default().event(event.name());
+                match event {
+                    ProcessEvent::New(p) | ProcessEvent::Exit(p) => {
+                        sse.json_data(JSONProcess::new(&p))
+                    },
+                    ProcessEvent::Change { process, changes } => {
+                        sse.json_data(JSONProcessChange::new(&process, changes))
+                    },
+                }
+            });
             warp::sse::reply(stream)
         });
 
+        let manager5 = manager.clone();
+        let stats_route = warp::path("stats").and(warp::get()).map(move || {
+            debug!("Called stats");
+            let manager = manager5.read();
+            let cache = manager.process_probe().process_cache();
+            warp::reply::json(&JSONStats {
+                processes: manager.process_probe().get_cached_processes().len(),
+                seen:      cache.seen_len(),
+                dedup:     cache.dedup_policy(),
+                timing:    cache.inner().current().and_then(|x| x.timing()),
+            })
+        });
+
+        let manager6 = manager.clone();
+        let snapshots_route =
+            warp::path("snapshots").and(warp::path::end()).and(warp::get()).map(move || {
+                debug!("Called snapshots");
+                let manager = manager6.read();
+                let snapshots = manager
+                    .process_probe()
+                    .process_cache()
+                    .inner()
+                    .snapshots()
+                    .map(|x| JSONSnapshotSummary {
+                        id:        x.id(),
+                        timestamp: x
+                            .timestamp()
+                            .duration_since(UNIX_EPOCH)
+                            .map(|x| x.as_millis() as u64)
+                            .unwrap_or_default(),
+                        processes: x.processes().len(),
+                        timing:    x.timing(),
+                    })
+                    .collect::<Vec<_>>();
+                warp::reply::json(&snapshots)
+            });
+
+        let manager7 = manager.clone();
+        let snapshot_route = warp::path!("snapshots" / u64).and(warp::get()).map(move |id: u64| {
+            debug!("Called snapshot {}", id);
+            let manager = manager7.read();
+            match manager.process_probe().process_cache().inner().snapshot(id) {
+                Some(snapshot) => warp::reply::json(&SnapshotRecord::new(snapshot)).into_response(),
+                None => warp::reply::with_status(
+                    warp::reply::json(&JSONError {
+                        error: format!("Generation {id} is not retained"),
+                    }),
+                    warp::http::StatusCode::NOT_FOUND,
+                )
+                .into_response(),
+            }
+        });
+
+        let manager8 = manager.clone();
+        let diff_route = warp::path("diff").and(warp::get()).and(warp::query::<DiffParams>()).map(
+            move |params: DiffParams| {
+                debug!("Called diff");
+                let manager = manager8.read();
+                match manager.process_probe().process_cache().inner().diff(params.from, params.to) {
+                    Some(diff) => warp::reply::json(&diff).into_response(),
+                    None => warp::reply::with_status(
+                        warp::reply::json(&JSONError {
+                            error: format!(
+                                "Generations {} and {} are not both retained",
+                                params.from, params.to
+                            ),
+                        }),
+                        warp::http::StatusCode::NOT_FOUND,
+                    )
+                    .into_response(),
+                }
+            },
+        );
+
+        let manager9 = manager.clone();
+        let scheduler_route =
+            warp::path("scheduler").and(warp::path::end()).and(warp::get()).map(move || {
+                debug!("Called scheduler");
+                let manager = manager9.read();
+                let scheduler = manager.scheduler();
+                warp::reply::json(&JSONScheduler {
+                    running: scheduler.is_some(),
+                    paused:  scheduler.map(|x| x.is_paused()).unwrap_or(false),
+                    config:  scheduler.map(|x| x.config().clone()),
+                })
+            });
+
+        let manager10 = manager.clone();
+        let scheduler_control_route =
+            warp::path!("scheduler" / String).and(warp::post()).map(move |action: String| {
+                debug!("Called scheduler {}", action);
+                let manager = manager10.read();
+                let Some(scheduler) = manager.scheduler() else {
+                    return warp::reply::with_status(
+                        warp::reply::json(&JSONError {
+                            error: "No scheduler is running".to_string(),
+                        }),
+                        warp::http::StatusCode::CONFLICT,
+                    )
+                    .into_response();
+                };
+                match action.as_str() {
+                    "pause" => scheduler.pause(),
+                    "resume" => scheduler.resume(),
+                    _ => {
+                        return warp::reply::with_status(
+                            warp::reply::json(&JSONError {
+                                error: format!("Unknown scheduler action {action}"),
+                            }),
+                            warp::http::StatusCode::NOT_FOUND,
+                        )
+                        .into_response()
+                    },
+                }
+                warp::reply::Response::default()
+            });
+
+        let manager11 = manager.clone();
+        let threads_route =
+            warp::path!("processes" / u32 / "threads").and(warp::get()).map(move |pid: u32| {
+                debug!("Called threads {}", pid);
+                reply_detail(&manager11.read(), pid, "threads", process_threads)
+            });
+
+        let manager12 = manager.clone();
+        let fds_route =
+            warp::path!("processes" / u32 / "fds").and(warp::get()).map(move |pid: u32| {
+                debug!("Called fds {}", pid);
+                reply_detail(&manager12.read(), pid, "fds", process_fds)
+            });
+
+        let manager13 = manager.clone();
+        let files_route = warp::path("files")
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(warp::query::<FilesParams>())
+            .map(move |params: FilesParams| {
+                debug!("Called files");
+                let filtered = params.path.is_some() || params.deleted.is_some();
+                reply_lookup(
+                    &manager13.read(),
+                    "fds",
+                    process_fds,
+                    filtered.then_some(|x: &JSONFd| params.matches(x)),
+                    |process, fd| JSONOpenFile { process, fd },
+                )
+            });
+
+        let manager14 = manager.clone();
+        let ports_route = warp::path("ports")
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(warp::query::<PortsParams>())
+            .map(move |params: PortsParams| {
+                debug!("Called ports");
+                // the listening ones by default
+                reply_lookup(
+                    &manager14.read(),
+                    "fds",
+                    process_sockets,
+                    Some(|x: &JSONSocket| params.matches(x)),
+                    |process, socket| JSONPort { process, socket },
+                )
+            });
+
+        let manager15 = manager.clone();
+        let namespaces_route = warp::path("namespaces").and(warp::get()).map(move || {
+            debug!("Called namespaces");
+            let manager = manager15.read();
+            let processes = manager.process_probe().get_cached_processes();
+            // Note: the processes are in pid order so are the groups by their first pid
+            let mut groups: Vec<JSONNamespaceGroup> = vec![];
+            let mut indexes = std::collections::HashMap::new();
+            for process in processes {
+                let Some(namespaces) = process_namespaces(process) else {
+                    continue;
+                };
+                let idx = *indexes.entry(namespaces.clone()).or_insert_with(|| {
+                    groups.push(JSONNamespaceGroup {
+                        namespaces,
+                        pids: vec![],
+                    });
+                    groups.len() - 1
+                });
+                groups[idx].pids.push(process.pid());
+            }
+            warp::reply::json(&groups)
+        });
+
+        let manager16 = manager.clone();
+        let mappings_route =
+            warp::path!("processes" / u32 / "libraries").and(warp::get()).map(move |pid: u32| {
+                debug!("Called libraries {}", pid);
+                reply_detail(&manager16.read(), pid, "maps", process_mappings)
+            });
+
+        let manager17 = manager.clone();
+        let libraries_route = warp::path("libraries")
+            .and(warp::path::end())
+            .and(warp::get())
+            .and(warp::query::<LibrariesParams>())
+            .map(move |params: LibrariesParams| {
+                debug!("Called libraries");
+                let filtered = params.path.is_some() || params.deleted.is_some();
+                reply_lookup(
+                    &manager17.read(),
+                    "maps",
+                    process_mappings,
+                    filtered.then_some(|x: &JSONMapping| params.matches(x)),
+                    |process, mapping| JSONMappedFile { process, mapping },
+                )
+            });
+
         let default_route = warp::get().and(warp::path::end()).map(|| MESSAGE);
 
-        let routes =
-            default_route.or(acquire_route).or(processes_route).or(search_route).or(data_route);
+        let routes = default_route
+            .or(acquire_route)
+            .or(processes_route)
+            .or(threads_route)
+            .or(fds_route)
+            .or(mappings_route)
+            .or(files_route)
+            .or(libraries_route)
+            .or(ports_route)
+            .or(search_route)
+            .or(namespaces_route)
+            .or(data_route)
+            .or(stats_route)
+            .or(snapshots_route)
+            .or(snapshot_route)
+            .or(diff_route)
+            .or(scheduler_route)
+            .or(scheduler_control_route);
 
         warp::serve(routes).run((self.config.address, self.config.port)).await
     }
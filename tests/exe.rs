@@ -0,0 +1,60 @@
+//! The status of the executable of a process as it is removed and replaced,
+//! this copies `sleep` so it runs outside of the shared temporary directory
+
+#[cfg(unix)]
+mod unix {
+    use libprocess_server::{ExeStatus, ProcRoot, StaticProcess, UnixProcess};
+    use std::{
+        path::{Path, PathBuf},
+        process::Command,
+        thread::sleep,
+        time::Duration,
+    };
+
+    fn find_sleep() -> PathBuf {
+        let path = std::env::var_os("PATH").expect("works");
+        std::env::split_paths(&path).map(|x| x.join("sleep")).find(|x| x.exists()).expect("works")
+    }
+
+    /// Wait for the child to execute `path`, its exe is the one of the test
+    /// until then
+    fn wait_exe(root: &ProcRoot, pid: u32, path: &Path) {
+        let link = root.process_path(pid).join("exe");
+        for _ in 0..1000 {
+            if link.read_link().ok().as_deref() == Some(path) {
+                return;
+            }
+            sleep(Duration::from_millis(1));
+        }
+        panic!("{pid} did not execute {path:?}");
+    }
+
+    #[test_log::test]
+    fn test_exe_status() {
+        let root = ProcRoot::default();
+        let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("process_server_exe");
+        std::fs::copy(find_sleep(), &path).expect("works");
+
+        let mut command = Command::new(&path).arg("10").spawn().expect("works");
+        let pid = command.id();
+        wait_exe(&root, pid, &path);
+        let process = UnixProcess::from_manual(pid).expect("works");
+        assert_eq!(
+            (process.exe(), process.exe_status()),
+            (path.clone(), ExeStatus::Present)
+        );
+        std::fs::remove_file(&path).expect("works");
+        let process = UnixProcess::from_manual(pid).expect("works");
+        assert_eq!(process.exe_status(), ExeStatus::Deleted);
+        std::fs::copy(root.process_path(pid).join("exe"), &path).expect("works");
+        let statuses = [
+            UnixProcess::from_manual(pid).expect("works").exe_status(),
+            UnixProcess::from_procfs(pid).expect("works").exe_status(),
+            UnixProcess::from_psutil(pid).expect("works").exe_status(),
+        ];
+        assert_eq!(statuses, [ExeStatus::Replaced; 3]);
+        command.kill().expect("works");
+        command.wait().expect("works");
+        std::fs::remove_file(&path).expect("works");
+    }
+}